@@ -0,0 +1,64 @@
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+// Unique per write, so two threads or processes writing the same file never
+// share a temp file.
+fn temp_path(path: &Path) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(format!(".{}-{}.tmp", std::process::id(), COUNT.fetch_add(1, Ordering::Relaxed)));
+    path.with_file_name(name)
+}
+
+// Writes to a sibling temp file, flushes it to disk and renames it over the
+// target, so readers see either the old or the new contents, never a mix.
+pub fn write(path: &Path, contents: &[u8]) -> Result<(), String> {
+    let tmp = temp_path(path);
+
+    let result = (|| {
+        let mut file = File::create(&tmp)
+            .map_err(|e| format!("Failed to create {}: {}", tmp.display(), e))?;
+        file.write_all(contents)
+            .map_err(|e| format!("Failed to write {}: {}", tmp.display(), e))?;
+        file.sync_all()
+            .map_err(|e| format!("Failed to flush {}: {}", tmp.display(), e))?;
+        drop(file);
+        fs::rename(&tmp, path)
+            .map_err(|e| format!("Failed to replace {}: {}", path.display(), e))
+    })();
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[test]
+    fn concurrent_writes_never_mix() {
+        let dir = TempDir::new("atomic-file");
+        let path = dir.path().join("cover");
+        let contents: Vec<Vec<u8>> = (0..4u8).map(|i| vec![i; 256 * 1024]).collect();
+
+        std::thread::scope(|scope| {
+            for contents in &contents {
+                let path = &path;
+                scope.spawn(move || {
+                    for _ in 0..10 {
+                        write(path, contents).unwrap();
+                    }
+                });
+            }
+        });
+
+        let written = fs::read(&path).unwrap();
+        assert!(contents.contains(&written));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+}
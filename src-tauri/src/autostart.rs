@@ -0,0 +1,383 @@
+use crate::settings::LaunchMode;
+
+pub trait AutostartBackend {
+    fn enable(&self, launch_mode: &LaunchMode) -> Result<(), String>;
+    fn disable(&self) -> Result<(), String>;
+    fn expected_command(&self, launch_mode: &LaunchMode) -> Result<String, String>;
+    fn current_command(&self) -> Result<Option<String>, String>;
+}
+
+fn current_exe() -> Result<std::path::PathBuf, String> {
+    std::env::current_exe().map_err(|e| format!("Failed to get executable path: {}", e))
+}
+
+// Arguments the login launch needs to start the same way as this instance.
+fn launch_args(launch_mode: &LaunchMode) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(flag) = launch_mode.as_flag() {
+        args.push(flag.to_string());
+    }
+    if let Some(dir) = crate::paths::config_dir_launch_arg() {
+        args.push("--config-dir".to_string());
+        args.push(dir.display().to_string());
+    }
+    args
+}
+
+#[cfg(target_os = "windows")]
+mod registry {
+    use super::{AutostartBackend, LaunchMode};
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    const RUN_KEY_PATH: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
+    const APP_NAME: &str = "QobuzPlayer";
+
+    pub struct RegistryAutostart;
+
+    impl AutostartBackend for RegistryAutostart {
+        fn enable(&self, launch_mode: &LaunchMode) -> Result<(), String> {
+            let command = self.expected_command(launch_mode)?;
+
+            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+            let run_key = hkcu
+                .open_subkey_with_flags(RUN_KEY_PATH, KEY_WRITE)
+                .map_err(|e| format!("Failed to open registry key: {}", e))?;
+
+            run_key
+                .set_value(APP_NAME, &command)
+                .map_err(|e| format!("Failed to set registry value: {}", e))?;
+
+            Ok(())
+        }
+
+        fn disable(&self) -> Result<(), String> {
+            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+            let run_key = hkcu
+                .open_subkey_with_flags(RUN_KEY_PATH, KEY_WRITE)
+                .map_err(|e| format!("Failed to open registry key: {}", e))?;
+
+            match run_key.delete_value(APP_NAME) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(format!("Failed to delete registry value: {}", e)),
+            }
+        }
+
+        fn expected_command(&self, launch_mode: &LaunchMode) -> Result<String, String> {
+            let exe_path = super::current_exe()?;
+
+            let mut command = format!("\"{}\"", exe_path.display());
+
+            for arg in super::launch_args(launch_mode) {
+                command.push(' ');
+                if arg.contains(' ') {
+                    command.push_str(&format!("\"{}\"", arg));
+                } else {
+                    command.push_str(&arg);
+                }
+            }
+
+            Ok(command)
+        }
+
+        fn current_command(&self) -> Result<Option<String>, String> {
+            let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+            let run_key = hkcu
+                .open_subkey_with_flags(RUN_KEY_PATH, KEY_READ)
+                .map_err(|e| format!("Failed to open registry key: {}", e))?;
+
+            match run_key.get_value::<String, _>(APP_NAME) {
+                Ok(command) => Ok(Some(command)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(format!("Failed to read registry value: {}", e)),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub use registry::RegistryAutostart;
+
+// Follows the freedesktop.org Desktop Application Autostart Specification:
+// a .desktop entry in $XDG_CONFIG_HOME/autostart is started on login.
+#[cfg(target_os = "linux")]
+pub mod xdg {
+    use super::{AutostartBackend, LaunchMode};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    const DESKTOP_FILE_NAME: &str = "qobuz-player.desktop";
+
+    pub struct XdgAutostart {
+        config_home: PathBuf,
+    }
+
+    impl XdgAutostart {
+        pub fn new(config_home: PathBuf) -> Self {
+            XdgAutostart { config_home }
+        }
+
+        pub fn from_env() -> Result<Self, String> {
+            let config_home = std::env::var_os("XDG_CONFIG_HOME")
+                .map(PathBuf::from)
+                .filter(|p| p.is_absolute())
+                .or_else(|| dirs::home_dir().map(|h| h.join(".config")))
+                .ok_or_else(|| "Could not determine XDG config directory".to_string())?;
+            Ok(Self::new(config_home))
+        }
+
+        pub fn desktop_file_path(&self) -> PathBuf {
+            self.config_home.join("autostart").join(DESKTOP_FILE_NAME)
+        }
+
+        pub fn exec_line(exe_path: &Path, launch_mode: &LaunchMode) -> String {
+            let mut exec = quote_exec_arg(&exe_path.to_string_lossy());
+            for arg in super::launch_args(launch_mode) {
+                exec.push(' ');
+                exec.push_str(&quote_exec_arg(&arg));
+            }
+            exec
+        }
+
+        pub fn desktop_entry(exe_path: &Path, launch_mode: &LaunchMode) -> String {
+            format!(
+                "[Desktop Entry]\n\
+                 Type=Application\n\
+                 Name=Qobuz Player\n\
+                 Comment=Web Container for Qobuz Web Player\n\
+                 Exec={}\n\
+                 Terminal=false\n\
+                 X-GNOME-Autostart-enabled=true\n",
+                Self::exec_line(exe_path, launch_mode)
+            )
+        }
+    }
+
+    // Exec= arguments containing reserved characters must be double-quoted, with
+    // `"`, `` ` ``, `$` and `\` backslash-escaped; `%` is always doubled.
+    fn quote_exec_arg(arg: &str) -> String {
+        let arg = arg.replace('%', "%%");
+        let reserved = [' ', '\t', '\n', '"', '\'', '\\', '>', '<', '~', '|', '&', ';', '$', '*', '?', '#', '(', ')', '`'];
+        if !arg.contains(reserved) {
+            return arg;
+        }
+
+        let mut quoted = String::with_capacity(arg.len() + 2);
+        quoted.push('"');
+        for c in arg.chars() {
+            if matches!(c, '"' | '`' | '$' | '\\') {
+                quoted.push('\\');
+            }
+            quoted.push(c);
+        }
+        quoted.push('"');
+        quoted
+    }
+
+    impl AutostartBackend for XdgAutostart {
+        fn enable(&self, launch_mode: &LaunchMode) -> Result<(), String> {
+            let exe_path = super::current_exe()?;
+            let path = self.desktop_file_path();
+
+            if let Some(dir) = path.parent() {
+                fs::create_dir_all(dir)
+                    .map_err(|e| format!("Failed to create autostart directory: {}", e))?;
+            }
+
+            fs::write(&path, Self::desktop_entry(&exe_path, launch_mode))
+                .map_err(|e| format!("Failed to write autostart entry: {}", e))?;
+
+            Ok(())
+        }
+
+        fn disable(&self) -> Result<(), String> {
+            let path = self.desktop_file_path();
+            if path.exists() {
+                fs::remove_file(&path)
+                    .map_err(|e| format!("Failed to remove autostart entry: {}", e))?;
+            }
+            Ok(())
+        }
+
+        fn expected_command(&self, launch_mode: &LaunchMode) -> Result<String, String> {
+            let exe_path = super::current_exe()?;
+            Ok(Self::exec_line(&exe_path, launch_mode))
+        }
+
+        // An entry with Hidden=true or X-GNOME-Autostart-enabled=false is
+        // treated by session managers as deleted, so report it as absent.
+        fn current_command(&self) -> Result<Option<String>, String> {
+            let path = self.desktop_file_path();
+            if !path.exists() {
+                return Ok(None);
+            }
+
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read autostart entry: {}", e))?;
+
+            let mut exec = None;
+            for line in contents.lines().map(str::trim) {
+                match line.split_once('=') {
+                    Some(("Exec", value)) => exec = Some(value.to_string()),
+                    Some(("Hidden", "true")) | Some(("X-GNOME-Autostart-enabled", "false")) => {
+                        return Ok(None);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(exec)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::test_support::TempDir;
+
+        #[test]
+        fn enabled_entry_reads_back_as_the_expected_command() {
+            let dir = TempDir::new("autostart");
+            let xdg = XdgAutostart::new(dir.path().to_path_buf());
+            assert_eq!(xdg.current_command().unwrap(), None);
+
+            xdg.enable(&LaunchMode::Minimized).unwrap();
+            assert_eq!(xdg.desktop_file_path(), dir.path().join("autostart").join(DESKTOP_FILE_NAME));
+            let expected = xdg.expected_command(&LaunchMode::Minimized).unwrap();
+            assert!(expected.ends_with(" --minimized"), "{}", expected);
+            assert_eq!(xdg.current_command().unwrap(), Some(expected));
+        }
+
+        #[test]
+        fn disable_removes_the_entry() {
+            let dir = TempDir::new("autostart");
+            let xdg = XdgAutostart::new(dir.path().to_path_buf());
+            xdg.enable(&LaunchMode::Restored).unwrap();
+
+            xdg.disable().unwrap();
+            assert!(!xdg.desktop_file_path().exists());
+            assert_eq!(xdg.current_command().unwrap(), None);
+            // Nothing left to remove is not an error.
+            xdg.disable().unwrap();
+        }
+
+        #[test]
+        fn hidden_entries_count_as_disabled() {
+            let dir = TempDir::new("autostart");
+            let xdg = XdgAutostart::new(dir.path().to_path_buf());
+            fs::create_dir_all(dir.path().join("autostart")).unwrap();
+
+            for flag in ["Hidden=true", "X-GNOME-Autostart-enabled=false"] {
+                let entry = format!("[Desktop Entry]\nType=Application\nExec=qobuz-player\n{}\n", flag);
+                fs::write(xdg.desktop_file_path(), entry).unwrap();
+                assert_eq!(xdg.current_command().unwrap(), None, "{}", flag);
+            }
+        }
+
+        #[test]
+        fn exec_arguments_are_quoted() {
+            let exec = XdgAutostart::exec_line(Path::new("/opt/Qobuz Player/qobuz-player"), &LaunchMode::MinimizedToTray);
+            assert_eq!(exec, "\"/opt/Qobuz Player/qobuz-player\" --minimized-to-tray");
+            assert_eq!(quote_exec_arg("/home/me/100%"), "/home/me/100%%");
+            assert_eq!(quote_exec_arg("/tmp/$HOME \"x\""), "\"/tmp/\\$HOME \\\"x\\\"\"");
+        }
+
+        #[test]
+        fn reconcile_creates_repairs_and_removes_the_entry() {
+            let dir = TempDir::new("autostart");
+            let xdg = XdgAutostart::new(dir.path().to_path_buf());
+            let expected = |mode: &LaunchMode| xdg.expected_command(mode).unwrap();
+
+            super::super::reconcile_with(&xdg, true, &LaunchMode::Restored).unwrap();
+            assert_eq!(xdg.current_command().unwrap(), Some(expected(&LaunchMode::Restored)));
+
+            super::super::reconcile_with(&xdg, true, &LaunchMode::Maximized).unwrap();
+            assert_eq!(xdg.current_command().unwrap(), Some(expected(&LaunchMode::Maximized)));
+
+            // An entry left by an older install at another path is rewritten.
+            fs::write(xdg.desktop_file_path(), "[Desktop Entry]\nExec=/old/qobuz-player\n").unwrap();
+            super::super::reconcile_with(&xdg, true, &LaunchMode::Maximized).unwrap();
+            assert_eq!(xdg.current_command().unwrap(), Some(expected(&LaunchMode::Maximized)));
+
+            super::super::reconcile_with(&xdg, false, &LaunchMode::Maximized).unwrap();
+            assert!(!xdg.desktop_file_path().exists());
+            super::super::reconcile_with(&xdg, false, &LaunchMode::Maximized).unwrap();
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use xdg::XdgAutostart;
+
+#[cfg(not(target_os = "windows"))]
+pub struct UnsupportedAutostart;
+
+#[cfg(not(target_os = "windows"))]
+impl AutostartBackend for UnsupportedAutostart {
+    fn enable(&self, _launch_mode: &LaunchMode) -> Result<(), String> {
+        Err("Autostart is not supported on this platform".to_string())
+    }
+
+    fn disable(&self) -> Result<(), String> {
+        Err("Autostart is not supported on this platform".to_string())
+    }
+
+    fn expected_command(&self, _launch_mode: &LaunchMode) -> Result<String, String> {
+        Err("Autostart is not supported on this platform".to_string())
+    }
+
+    fn current_command(&self) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+}
+
+#[cfg(target_os = "windows")]
+pub fn backend() -> Box<dyn AutostartBackend> {
+    Box::new(RegistryAutostart)
+}
+
+#[cfg(target_os = "linux")]
+pub fn backend() -> Box<dyn AutostartBackend> {
+    match XdgAutostart::from_env() {
+        Ok(xdg) => Box::new(xdg),
+        Err(_) => Box::new(UnsupportedAutostart),
+    }
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "linux")))]
+pub fn backend() -> Box<dyn AutostartBackend> {
+    Box::new(UnsupportedAutostart)
+}
+
+// Compares the OS autostart entry with the settings and rewrites or removes it
+// when they disagree. An Err means the entry is still out of sync afterwards.
+pub fn reconcile(launch_on_login: bool, launch_mode: &LaunchMode) -> Result<(), String> {
+    reconcile_with(backend().as_ref(), launch_on_login, launch_mode)
+}
+
+fn reconcile_with(backend: &dyn AutostartBackend, launch_on_login: bool, launch_mode: &LaunchMode) -> Result<(), String> {
+    let current = backend.current_command()?;
+
+    if launch_on_login {
+        let expected = backend.expected_command(launch_mode)?;
+        if current.as_deref() == Some(expected.as_str()) {
+            return Ok(());
+        }
+
+        backend.enable(launch_mode)
+            .map_err(|e| format!("Launch on login is enabled but the autostart entry could not be updated: {}", e))?;
+
+        if backend.current_command()?.as_deref() != Some(expected.as_str()) {
+            return Err("Launch on login is enabled but the autostart entry does not match after repair".to_string());
+        }
+    } else if current.is_some() {
+        backend.disable()
+            .map_err(|e| format!("Launch on login is disabled but the autostart entry could not be removed: {}", e))?;
+
+        if backend.current_command()?.is_some() {
+            return Err("Launch on login is disabled but the autostart entry is still present".to_string());
+        }
+    }
+
+    Ok(())
+}
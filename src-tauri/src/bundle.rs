@@ -0,0 +1,301 @@
+use crate::config::{LayeredConfig, Resolved};
+use crate::settings::Settings;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+const BUNDLE_FORMAT: &str = "qobuz-player-config";
+const BUNDLE_VERSION: u32 = 1;
+
+// User files kept in the config directory next to settings.json that travel
+// with a bundle. Only these names are ever read from or written to a bundle.
+const USER_FILES: &[&str] = &[crate::selectors::USER_PACK_FILE];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConfigBundle {
+    format: String,
+    bundle_version: u32,
+    app_version: String,
+    settings: Value,
+    #[serde(default)]
+    files: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SettingChange {
+    pub key: String,
+    pub current: Option<Value>,
+    pub incoming: Option<Value>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportPreview {
+    pub path: String,
+    pub app_version: String,
+    pub changes: Vec<SettingChange>,
+    pub files: Vec<String>,
+}
+
+impl ConfigBundle {
+    pub fn export(file_settings: &Settings, config_dir: &Path) -> Result<Self, String> {
+        let mut files = BTreeMap::new();
+        for name in USER_FILES {
+            let path = config_dir.join(name);
+            if path.is_file() {
+                let contents = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                files.insert(name.to_string(), contents);
+            }
+        }
+
+        Ok(ConfigBundle {
+            format: BUNDLE_FORMAT.to_string(),
+            bundle_version: BUNDLE_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            // Secrets stay on this machine.
+            settings: serde_json::to_value(file_settings.redacted())
+                .map_err(|e| format!("Failed to serialize settings: {}", e))?,
+            files,
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| format!("Failed to serialize configuration bundle: {}", e))?;
+        crate::atomic_file::write(path, json.as_bytes())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let bundle: ConfigBundle = serde_json::from_str(&contents)
+            .map_err(|e| format!("{} is not a configuration bundle: {}", path.display(), e))?;
+        bundle.validate()?;
+        Ok(bundle)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.format != BUNDLE_FORMAT {
+            return Err(format!("Unknown bundle format \"{}\"", self.format));
+        }
+        if self.bundle_version > BUNDLE_VERSION {
+            return Err(format!(
+                "Bundle version {} is newer than this build supports ({})",
+                self.bundle_version, BUNDLE_VERSION
+            ));
+        }
+        for name in self.files.keys() {
+            if !USER_FILES.contains(&name.as_str()) {
+                return Err(format!("Bundle contains unsupported file \"{}\"", name));
+            }
+        }
+        self.settings().map(|_| ())
+    }
+
+    // Settings go through the same migrations as settings.json, so bundles
+    // exported by older versions import cleanly.
+    pub fn settings(&self) -> Result<Settings, String> {
+        let json = serde_json::to_string(&self.settings)
+            .map_err(|e| format!("Failed to read bundle settings: {}", e))?;
+        Settings::from_json(&json).map(|(settings, _)| settings)
+    }
+
+    // What importing over `current` would save: secrets left out of the
+    // bundle are kept from `current`, and a service whose secret this
+    // machine does not have is imported switched off.
+    pub fn settings_for(&self, current: &Settings) -> Result<Settings, String> {
+        let mut settings = self.settings()?;
+        settings.restore_secrets(current);
+        if settings.scrobbling.session_key.is_empty() {
+            settings.scrobbling.enabled = false;
+        }
+        if settings.listenbrainz.token.is_empty() {
+            settings.listenbrainz.enabled = false;
+        }
+        if settings.remote_api.token.is_empty() {
+            settings.remote_api.enabled = false;
+        }
+        Ok(settings)
+    }
+
+    pub fn preview(&self, path: &Path, current: &Settings) -> Result<ImportPreview, String> {
+        let current_map = current.redacted().to_map()?;
+        let incoming_map = self.settings_for(current)?.redacted().to_map()?;
+
+        let mut keys: Vec<&String> = current_map.keys().chain(incoming_map.keys()).collect();
+        keys.sort();
+        keys.dedup();
+
+        let changes = keys
+            .into_iter()
+            .filter(|key| *key != "version" && current_map.get(*key) != incoming_map.get(*key))
+            .map(|key| SettingChange {
+                key: key.clone(),
+                current: current_map.get(key).cloned(),
+                incoming: incoming_map.get(key).cloned(),
+            })
+            .collect();
+
+        Ok(ImportPreview {
+            path: path.display().to_string(),
+            app_version: self.app_version.clone(),
+            changes,
+            files: self.files.keys().cloned().collect(),
+        })
+    }
+
+    pub fn write_files(&self, config_dir: &Path) -> Result<(), String> {
+        for (name, contents) in &self.files {
+            crate::atomic_file::write(&config_dir.join(name), contents.as_bytes())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn import(bundle: &ConfigBundle, config: &mut LayeredConfig, config_dir: &Path) -> Result<Resolved, String> {
+    let incoming = bundle.settings_for(config.file())?;
+    // Checked before any user file is written so a rejected bundle changes
+    // nothing.
+    config.check_file(&incoming)?;
+    bundle.write_files(config_dir)?;
+    config.save_file(incoming)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::policy::Policy;
+    use crate::settings::REDACTED;
+    use crate::test_support::TempDir;
+    use std::path::PathBuf;
+
+    const SELECTORS: &str = r#"{ "format": "qobuz-player-selectors", "version": 1, "actions": {} }"#;
+
+    // A config directory with settings.json written through the config.
+    struct Machine {
+        dir: TempDir,
+        config: LayeredConfig,
+    }
+
+    impl Machine {
+        fn new(settings: Settings, policy: &str) -> Self {
+            let dir = TempDir::new("bundle");
+            let config = LayeredConfig::new(
+                Settings::default(),
+                Vec::new(),
+                Default::default(),
+                Default::default(),
+                Policy::from_json(policy).unwrap(),
+            )
+            .with_path(dir.path().join("settings.json"));
+            let mut machine = Machine { dir, config };
+            machine.config.save_file(settings).unwrap();
+            machine
+        }
+
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.path().join(name)
+        }
+
+        fn saved(&self) -> Settings {
+            Settings::from_json(&fs::read_to_string(self.path("settings.json")).unwrap()).unwrap().0
+        }
+
+        fn export(&self) -> ConfigBundle {
+            let path = self.path("bundle.json");
+            ConfigBundle::export(self.config.file(), self.dir.path()).unwrap().write(&path).unwrap();
+            ConfigBundle::read(&path).unwrap()
+        }
+
+        fn import(&mut self, bundle: &ConfigBundle) -> Result<Resolved, String> {
+            import(bundle, &mut self.config, self.dir.path())
+        }
+    }
+
+    fn with_remote_api(token: &str) -> Settings {
+        let mut settings = Settings::default();
+        settings.remote_api.enabled = true;
+        settings.remote_api.token = token.to_string();
+        settings
+    }
+
+    #[test]
+    fn export_then_import_round_trips() {
+        let mut settings = Settings::default();
+        settings.close_to_tray = false;
+        settings.launch_mode = crate::settings::LaunchMode::Maximized;
+        settings.discord.client_id = "1234".to_string();
+        let source = Machine::new(settings.clone(), "{}");
+        fs::write(source.path(USER_FILES[0]), SELECTORS).unwrap();
+
+        let mut target = Machine::new(Settings::default(), "{}");
+        let bundle = source.export();
+        assert_eq!(bundle.preview(Path::new("bundle.json"), target.config.file()).unwrap().files, USER_FILES);
+
+        let resolved = target.import(&bundle).unwrap();
+        assert_eq!(resolved.settings, settings);
+        assert_eq!(target.saved(), settings);
+        assert_eq!(fs::read_to_string(target.path(USER_FILES[0])).unwrap(), SELECTORS);
+    }
+
+    #[test]
+    fn secrets_never_leave_the_machine() {
+        let source = Machine::new(with_remote_api("source-token-0123456789"), "{}");
+        source.export();
+        let written = fs::read_to_string(source.path("bundle.json")).unwrap();
+        assert!(!written.contains("source-token-0123456789"));
+        assert!(written.contains(REDACTED));
+
+        // A machine with its own token keeps it and the service stays on.
+        let mut target = Machine::new(with_remote_api("target-token-0123456789"), "{}");
+        let bundle = source.export();
+        let preview = bundle.preview(Path::new("bundle.json"), target.config.file()).unwrap();
+        assert!(preview.changes.is_empty(), "{:?}", preview.changes);
+        target.import(&bundle).unwrap();
+        assert_eq!(target.saved().remote_api, with_remote_api("target-token-0123456789").remote_api);
+
+        // One without a token imports the service switched off.
+        let mut target = Machine::new(Settings::default(), "{}");
+        target.import(&bundle).unwrap();
+        let saved = target.saved();
+        assert!(!saved.remote_api.enabled);
+        assert_eq!(saved.remote_api.token, "");
+    }
+
+    #[test]
+    fn import_refused_by_policy_changes_nothing() {
+        let mut settings = Settings::default();
+        settings.close_to_tray = false;
+        let source = Machine::new(settings, "{}");
+        fs::write(source.path(USER_FILES[0]), SELECTORS).unwrap();
+
+        let mut target = Machine::new(Settings::default(), r#"{ "close_to_tray": true }"#);
+        let before = fs::read_to_string(target.path("settings.json")).unwrap();
+        assert_eq!(
+            target.import(&source.export()).unwrap_err(),
+            "\"close_to_tray\" is locked by the administrator policy"
+        );
+        assert_eq!(fs::read_to_string(target.path("settings.json")).unwrap(), before);
+        assert!(!target.path(USER_FILES[0]).exists());
+    }
+
+    #[test]
+    fn invalid_import_changes_nothing() {
+        let mut settings = Settings::default();
+        settings.keybindings.play_pause = Some("Ctrl+Alt+P".to_string());
+        settings.keybindings.next = Some("Ctrl+Alt+P".to_string());
+        let source = Machine::new(Settings::default(), "{}");
+        fs::write(source.path(USER_FILES[0]), SELECTORS).unwrap();
+        let bundle = ConfigBundle::export(&settings, source.dir.path()).unwrap();
+
+        let mut target = Machine::new(Settings::default(), "{}");
+        let before = fs::read_to_string(target.path("settings.json")).unwrap();
+        let error = target.import(&bundle).unwrap_err();
+        assert!(error.contains("is assigned to both"), "{}", error);
+        assert_eq!(fs::read_to_string(target.path("settings.json")).unwrap(), before);
+        assert!(!target.path(USER_FILES[0]).exists());
+        assert_eq!(target.config.file(), &Settings::default());
+    }
+}
@@ -0,0 +1,130 @@
+use crate::control::ControlCommand;
+use crate::settings::LaunchMode;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Default)]
+pub struct CliArgs {
+    pub launch_mode: Option<LaunchMode>,
+    pub config_dir: Option<PathBuf>,
+    pub set: Vec<(String, String)>,
+    pub export_config: Option<PathBuf>,
+    pub import_config: Option<PathBuf>,
+    pub command: Option<ControlCommand>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CliAction {
+    Run(CliArgs),
+    Help,
+    Version,
+}
+
+pub fn usage() -> String {
+    format!(
+        r#"Qobuz Player {}
+
+Usage: qobuz-player [OPTIONS] [COMMAND]
+
+Commands (sent to the running instance; only show and open start one):
+  play-pause            Toggle playback
+  play, pause           Start or pause playback
+  next                  Skip to the next track
+  previous              Go back to the previous track
+  seek <SECONDS>        Jump to a position in the current track
+  volume <0-100>        Set the volume
+  shuffle               Toggle shuffle
+  repeat                Cycle the repeat mode
+  favorite              Add or remove the current track from favorites
+  show                  Show and focus the window
+  hide                  Hide the window to the tray
+  quit                  Quit the player
+  open <URL>            Open a play.qobuz.com or open.qobuz.com link
+
+Options:
+  --minimized           Start minimized to the taskbar
+  --minimized-to-tray   Start hidden in the system tray
+  --maximized           Start with a maximized window
+  --config-dir <DIR>    Read and write settings in DIR instead of the
+                        default location (also QOBUZ_PLAYER_CONFIG_DIR)
+  --set <KEY=VALUE>     Override a setting for this run without saving it,
+                        e.g. --set close_to_tray=false (repeatable)
+  --export-config <FILE> Write settings and user files to a bundle and exit
+  --import-config <FILE> Show the changes in a bundle, apply it and exit
+  -h, --help            Print this help and exit
+  -V, --version         Print the version and exit"#,
+        env!("CARGO_PKG_VERSION")
+    )
+}
+
+pub fn version() -> String {
+    format!("qobuz-player {}", env!("CARGO_PKG_VERSION"))
+}
+
+pub fn parse<I>(args: I) -> Result<CliAction, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut parsed = CliArgs::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(CliAction::Help),
+            "-V" | "--version" => return Ok(CliAction::Version),
+            "--config-dir" => {
+                let dir = args.next()
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| "--config-dir requires a directory".to_string())?;
+                parsed.config_dir = Some(PathBuf::from(dir));
+            }
+            "--set" => {
+                let assignment = args.next()
+                    .ok_or_else(|| "--set requires KEY=VALUE".to_string())?;
+                let (key, value) = assignment.split_once('=')
+                    .filter(|(key, _)| !key.is_empty())
+                    .ok_or_else(|| format!("Invalid --set value \"{}\", expected KEY=VALUE", assignment))?;
+                parsed.set.push((key.to_string(), value.to_string()));
+            }
+            "--export-config" | "--import-config" => {
+                let path = args.next()
+                    .filter(|v| !v.is_empty())
+                    .ok_or_else(|| format!("{} requires a file", arg))?;
+                if arg == "--export-config" {
+                    parsed.export_config = Some(PathBuf::from(path));
+                } else {
+                    parsed.import_config = Some(PathBuf::from(path));
+                }
+            }
+            _ if arg.starts_with("--config-dir=") => {
+                let dir = &arg["--config-dir=".len()..];
+                if dir.is_empty() {
+                    return Err("--config-dir requires a directory".to_string());
+                }
+                parsed.config_dir = Some(PathBuf::from(dir));
+            }
+            _ if !arg.starts_with('-') => {
+                let command = ControlCommand::parse(&arg, &mut args)?
+                    .ok_or_else(|| format!("Unknown command: {}", arg))?;
+                if parsed.command.is_some() {
+                    return Err(format!("Only one command can be given, got another: {}", arg));
+                }
+                parsed.command = Some(command);
+            }
+            _ => {
+                let mode = LaunchMode::from_flag(&arg)
+                    .ok_or_else(|| format!("Unknown argument: {}", arg))?;
+                if let Some(existing) = &parsed.launch_mode
+                    && *existing != mode {
+                        return Err(format!(
+                            "Conflicting launch flags: {} and {}",
+                            existing.as_flag().unwrap_or_default(),
+                            arg
+                        ));
+                    }
+                parsed.launch_mode = Some(mode);
+            }
+        }
+    }
+
+    Ok(CliAction::Run(parsed))
+}
@@ -0,0 +1,314 @@
+use crate::policy::Policy;
+use crate::settings::Settings;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+const ENV_PREFIX: &str = "QOBUZ_PLAYER_";
+// QOBUZ_PLAYER_* variables that configure the app itself rather than Settings.
+const RESERVED_ENV: &[&str] = &["QOBUZ_PLAYER_CONFIG_DIR", "QOBUZ_PLAYER_POLICY"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Default,
+    File,
+    Environment,
+    CommandLine,
+    Policy,
+}
+
+// Settings are resolved from, lowest to highest precedence: built-in
+// defaults, settings.json, QOBUZ_PLAYER_* environment variables, --set
+// command-line overrides and finally the administrator policy. Only the file
+// layer is ever written back to disk.
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    file: Settings,
+    file_keys: Vec<String>,
+    environment: Map<String, Value>,
+    command_line: Map<String, Value>,
+    policy: Policy,
+    // Where settings.json is written; None is the usual location in the
+    // config directory.
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolved {
+    pub settings: Settings,
+    pub sources: BTreeMap<String, Source>,
+}
+
+fn parse_value(raw: &str) -> Value {
+    serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+}
+
+fn validate_layer(layer: &Map<String, Value>, name: &str) -> Result<(), String> {
+    let mut map = Settings::default().to_map()?;
+    if let Some(key) = layer.keys().find(|key| *key == "version" || !map.contains_key(*key)) {
+        return Err(format!("Unknown setting \"{}\" in {}", key, name));
+    }
+    crate::policy::merge(&mut map, layer);
+    let applied = Settings::from_map(map)
+        .map_err(|e| format!("{} in {}", e, name))?
+        .to_map()?;
+    // A nested key the settings do not have is dropped by serde, so it shows
+    // up as a mismatch here.
+    match crate::policy::mismatch(layer, &applied, "") {
+        Some(key) => Err(format!("Unknown setting \"{}\" in {}", key, name)),
+        None => Ok(()),
+    }
+}
+
+pub fn environment_overrides() -> Result<Map<String, Value>, String> {
+    let mut layer = Map::new();
+    for (name, value) in std::env::vars() {
+        if !name.starts_with(ENV_PREFIX) || RESERVED_ENV.contains(&name.as_str()) {
+            continue;
+        }
+        let key = name[ENV_PREFIX.len()..].to_ascii_lowercase();
+        layer.insert(key, parse_value(&value));
+    }
+    validate_layer(&layer, "environment").map(|_| layer)
+}
+
+pub fn command_line_overrides(assignments: &[(String, String)]) -> Result<Map<String, Value>, String> {
+    let mut layer = Map::new();
+    for (key, value) in assignments {
+        layer.insert(key.clone(), parse_value(value));
+    }
+    validate_layer(&layer, "--set").map(|_| layer)
+}
+
+impl LayeredConfig {
+    pub fn new(
+        file: Settings,
+        file_keys: Vec<String>,
+        environment: Map<String, Value>,
+        command_line: Map<String, Value>,
+        policy: Policy,
+    ) -> Self {
+        LayeredConfig { file, file_keys, environment, command_line, policy, path: None }
+    }
+
+    // Writes settings.json to `path` instead of the config directory.
+    #[cfg(test)]
+    pub fn with_path(self, path: PathBuf) -> Self {
+        LayeredConfig { path: Some(path), ..self }
+    }
+
+    pub fn policy(&self) -> &Policy {
+        &self.policy
+    }
+
+    pub fn file(&self) -> &Settings {
+        &self.file
+    }
+
+    pub fn set_file(&mut self, file: Settings, file_keys: Vec<String>) {
+        self.file = file;
+        self.file_keys = file_keys;
+    }
+
+    pub fn resolve(&self) -> Result<Resolved, String> {
+        let mut map = self.file.to_map()?;
+        let mut sources = BTreeMap::new();
+
+        for key in map.keys() {
+            if key == "version" {
+                continue;
+            }
+            let source = if self.file_keys.contains(key) { Source::File } else { Source::Default };
+            sources.insert(key.clone(), source);
+        }
+
+        // Objects are merged key by key, so --set 'remote_api={"enabled":true}'
+        // keeps the port and token from the file. Their sources are then
+        // reported per key, e.g. "remote_api.enabled".
+        for (layer, source) in [(&self.environment, Source::Environment), (&self.command_line, Source::CommandLine)] {
+            crate::policy::merge(&mut map, layer);
+            let mut paths = Vec::new();
+            crate::policy::leaf_paths(layer, "", &mut paths);
+            for path in paths {
+                sources.insert(path, source);
+            }
+        }
+
+        let settings = self.policy.apply(&Settings::from_map(map)?)?;
+        for key in self.policy.locked_fields() {
+            sources.insert(key, Source::Policy);
+        }
+
+        Ok(Resolved { settings, sources })
+    }
+
+    // Turns settings edited in the overlay back into what belongs in
+    // settings.json: fields currently overridden by the environment, the
+    // command line or the policy keep their on-disk value instead of the
+    // override.
+    fn file_settings_for_save(&self, edited: &Settings) -> Result<Settings, String> {
+        self.policy.check(edited)?;
+
+        let file_map = self.file.to_map()?;
+        let mut map = edited.to_map()?;
+        crate::policy::restore(&self.environment, &mut map, &file_map);
+        crate::policy::restore(&self.command_line, &mut map, &file_map);
+        self.policy.restore_file_values(&mut map, &file_map);
+        Settings::from_map(map)
+    }
+
+    // The write path behind save_settings: strips session overrides, writes
+    // settings.json and returns the new effective settings. Side effects such
+    // as autostart are up to the caller.
+    pub fn save(&mut self, edited: &Settings) -> Result<Resolved, String> {
+        let file_settings = self.file_settings_for_save(edited)?;
+        self.write_file(file_settings)
+    }
+
+    // Whether settings.json contents from elsewhere, e.g. an imported bundle,
+    // may replace the current file: they must be valid and agree with the
+    // policy, just like settings saved from the overlay.
+    pub fn check_file(&self, file_settings: &Settings) -> Result<(), String> {
+        self.policy.check(file_settings)?;
+        file_settings.validate()
+    }
+
+    // Like save, but takes the exact settings.json contents, e.g. from an
+    // imported bundle, instead of settings edited on top of the overrides.
+    pub fn save_file(&mut self, file_settings: Settings) -> Result<Resolved, String> {
+        self.check_file(&file_settings)?;
+        self.write_file(file_settings)
+    }
+
+    // The policy is only applied in resolve, so the file keeps the user's
+    // values and they come back once the policy is lifted.
+    fn write_file(&mut self, file_settings: Settings) -> Result<Resolved, String> {
+        match &self.path {
+            Some(path) => file_settings.save_to(path)?,
+            None => file_settings.save()?,
+        }
+
+        let file_keys = file_settings.to_map()?.keys()
+            .filter(|k| *k != "version")
+            .cloned()
+            .collect();
+        self.set_file(file_settings, file_keys);
+        self.resolve()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+    use serde_json::json;
+
+    fn layer(value: Value) -> Map<String, Value> {
+        match value {
+            Value::Object(map) => map,
+            _ => panic!("layer must be an object"),
+        }
+    }
+
+    fn config(file: Value, environment: Value, command_line: Value, policy: &str) -> LayeredConfig {
+        let (file, file_keys) = Settings::from_json(&file.to_string()).unwrap();
+        LayeredConfig::new(
+            file,
+            file_keys,
+            layer(environment),
+            layer(command_line),
+            Policy::from_json(policy).unwrap(),
+        )
+    }
+
+    #[test]
+    fn later_layers_win() {
+        let file = json!({ "remote_api": { "port": 1001 } });
+        let environment = json!({ "remote_api": { "port": 1002 } });
+        let command_line = json!({ "remote_api": { "port": 1003 } });
+        let policy = r#"{ "remote_api": { "port": 1004 } }"#;
+
+        let port = |config: LayeredConfig| config.resolve().unwrap().settings.remote_api.port;
+        assert_eq!(port(config(json!({}), json!({}), json!({}), "{}")), Settings::default().remote_api.port);
+        assert_eq!(port(config(file.clone(), json!({}), json!({}), "{}")), 1001);
+        assert_eq!(port(config(file.clone(), environment.clone(), json!({}), "{}")), 1002);
+        assert_eq!(port(config(file.clone(), environment.clone(), command_line.clone(), "{}")), 1003);
+        assert_eq!(port(config(file, environment, command_line, policy)), 1004);
+    }
+
+    #[test]
+    fn sources_are_reported_per_field() {
+        let config = config(
+            json!({ "close_to_tray": false }),
+            json!({ "launch_mode": "maximized" }),
+            json!({ "remote_api": { "enabled": true } }),
+            r#"{ "discord": { "enabled": false } }"#,
+        );
+        let sources = config.resolve().unwrap().sources;
+
+        assert_eq!(sources["close_to_tray"], Source::File);
+        assert_eq!(sources["minimize_to_tray"], Source::Default);
+        assert_eq!(sources["launch_mode"], Source::Environment);
+        assert_eq!(sources["remote_api"], Source::Default);
+        assert_eq!(sources["remote_api.enabled"], Source::CommandLine);
+        assert_eq!(sources["discord.enabled"], Source::Policy);
+        assert!(!sources.contains_key("remote_api.port"));
+    }
+
+    #[test]
+    fn nested_overrides_keep_the_other_keys() {
+        let config = config(
+            json!({ "remote_api": { "port": 9000, "token": "secret-token" } }),
+            json!({}),
+            json!({ "remote_api": { "enabled": true } }),
+            "{}",
+        );
+        let remote_api = config.resolve().unwrap().settings.remote_api;
+
+        assert!(remote_api.enabled);
+        assert_eq!(remote_api.port, 9000);
+        assert_eq!(remote_api.token, "secret-token");
+    }
+
+    #[test]
+    fn save_never_writes_overrides() {
+        let dir = TempDir::new("config");
+        let path = dir.path().join("settings.json");
+        let mut config = config(
+            json!({ "close_to_tray": true, "remote_api": { "port": 9000 } }),
+            json!({ "close_to_tray": false }),
+            json!({ "remote_api": { "enabled": true, "token": "session-token" } }),
+            r#"{ "discord": { "enabled": true } }"#,
+        )
+        .with_path(path.clone());
+
+        let mut edited = config.resolve().unwrap().settings;
+        edited.minimize_to_tray = true;
+        edited.remote_api.port = 9001;
+        let resolved = config.save(&edited).unwrap();
+
+        let (saved, _) = Settings::from_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(saved.minimize_to_tray);
+        assert!(saved.close_to_tray);
+        assert_eq!(saved.remote_api.port, 9001);
+        assert!(!saved.remote_api.enabled);
+        assert_eq!(saved.remote_api.token, "");
+        assert!(!saved.discord.enabled);
+
+        // The overrides still apply to the session.
+        assert!(!resolved.settings.close_to_tray);
+        assert!(resolved.settings.remote_api.enabled);
+        assert!(resolved.settings.discord.enabled);
+    }
+
+    #[test]
+    fn unknown_nested_override_is_rejected() {
+        let assignments = vec![("remote_api".to_string(), r#"{"enable":true}"#.to_string())];
+        assert_eq!(
+            command_line_overrides(&assignments).unwrap_err(),
+            "Unknown setting \"remote_api.enable\" in --set"
+        );
+    }
+}
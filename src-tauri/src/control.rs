@@ -0,0 +1,111 @@
+use crate::player::PlayerCommand;
+use tauri::Manager;
+
+// Links a second invocation may open; open.qobuz.com share links are
+// rewritten to the web player, which serves the same paths.
+const PLAYER_HOST: &str = "play.qobuz.com";
+const SHARE_HOST: &str = "open.qobuz.com";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlCommand {
+    Player(PlayerCommand),
+    Show,
+    Hide,
+    Quit,
+    Open(tauri::Url),
+}
+
+impl ControlCommand {
+    pub fn parse<I>(name: &str, rest: &mut I) -> Result<Option<Self>, String>
+    where
+        I: Iterator<Item = String>,
+    {
+        let command = match name {
+            "play-pause" => ControlCommand::Player(PlayerCommand::Toggle),
+            "play" => ControlCommand::Player(PlayerCommand::Play),
+            "pause" => ControlCommand::Player(PlayerCommand::Pause),
+            "next" => ControlCommand::Player(PlayerCommand::Next),
+            "previous" => ControlCommand::Player(PlayerCommand::Previous),
+            "shuffle" => ControlCommand::Player(PlayerCommand::ToggleShuffle),
+            "repeat" => ControlCommand::Player(PlayerCommand::CycleRepeat),
+            "favorite" => ControlCommand::Player(PlayerCommand::ToggleFavorite),
+            "seek" => {
+                let position = parse_number(name, rest.next())?;
+                ControlCommand::Player(PlayerCommand::Seek { position })
+            }
+            "volume" => {
+                let percent = parse_number(name, rest.next())?;
+                if !(0.0..=100.0).contains(&percent) {
+                    return Err("volume must be between 0 and 100".to_string());
+                }
+                ControlCommand::Player(PlayerCommand::SetVolume { volume: percent / 100.0 })
+            }
+            "show" => ControlCommand::Show,
+            "hide" => ControlCommand::Hide,
+            "quit" => ControlCommand::Quit,
+            "open" => {
+                let url = rest.next().ok_or_else(|| "open requires a URL".to_string())?;
+                ControlCommand::Open(parse_qobuz_url(&url)?)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(command))
+    }
+
+    // Show and open can start the player; the rest only make sense for one
+    // that is already running.
+    pub fn needs_running_instance(&self) -> bool {
+        !matches!(self, ControlCommand::Show | ControlCommand::Open(_))
+    }
+}
+
+fn parse_number(command: &str, value: Option<String>) -> Result<f64, String> {
+    let value = value.ok_or_else(|| format!("{} requires a number", command))?;
+    value.parse::<f64>()
+        .ok()
+        .filter(|v| v.is_finite() && *v >= 0.0)
+        .ok_or_else(|| format!("{} requires a non-negative number, got \"{}\"", command, value))
+}
+
+fn parse_qobuz_url(raw: &str) -> Result<tauri::Url, String> {
+    let mut url = tauri::Url::parse(raw).map_err(|e| format!("Invalid URL \"{}\": {}", raw, e))?;
+    if url.scheme() != "https" {
+        return Err(format!("Only https Qobuz links can be opened: {}", raw));
+    }
+    match url.host_str() {
+        Some(PLAYER_HOST) => {}
+        Some(SHARE_HOST) => {
+            url.set_host(Some(PLAYER_HOST))
+                .map_err(|e| format!("Invalid URL \"{}\": {}", raw, e))?;
+        }
+        _ => return Err(format!("Only {} and {} links can be opened: {}", PLAYER_HOST, SHARE_HOST, raw)),
+    }
+    Ok(url)
+}
+
+pub fn run(app: &tauri::AppHandle, command: &ControlCommand) -> Result<(), String> {
+    let window = app.get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+
+    match command {
+        ControlCommand::Player(command) => crate::player::dispatch(app, command),
+        ControlCommand::Show => {
+            crate::window_manager::show_main_window(app);
+            Ok(())
+        }
+        ControlCommand::Hide => window.hide().map_err(|e| format!("Failed to hide window: {}", e)),
+        ControlCommand::Quit => quit(),
+        ControlCommand::Open(url) => {
+            window.navigate(url.clone())
+                .map_err(|e| format!("Failed to open {}: {}", url, e))?;
+            crate::window_manager::show_main_window(app);
+            Ok(())
+        }
+    }
+}
+
+pub fn quit() -> ! {
+    crate::thumbar::cleanup_thumbar();
+    crate::window_manager::remove_minimize_hook();
+    std::process::exit(0);
+}
@@ -0,0 +1,143 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const MAX_COVER_BYTES: u64 = 10 * 1024 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+// Covers kept on disk; the least recently fetched go first.
+const MAX_CACHED_COVERS: usize = 64;
+
+// Album covers shared by the tray icon and the now-playing files, kept in the
+// cache directory so a track that comes round again is not downloaded again.
+pub fn fetch(url: &str) -> Result<Vec<u8>, String> {
+    match crate::paths::cache_dir() {
+        Ok(dir) => fetch_in(&dir.join("covers"), url),
+        Err(e) => {
+            eprintln!("{}", e);
+            download(url)
+        }
+    }
+}
+
+fn fetch_in(dir: &Path, url: &str) -> Result<Vec<u8>, String> {
+    let path = dir.join(format!("{:x}", md5::compute(url.as_bytes())));
+    if let Ok(bytes) = std::fs::read(&path) {
+        touch(&path);
+        return Ok(bytes);
+    }
+
+    let bytes = download(url)?;
+    // The cover is still usable when it can't be cached.
+    let stored = std::fs::create_dir_all(dir)
+        .map_err(|e| format!("Failed to create directory {}: {}", dir.display(), e))
+        .and_then(|()| crate::atomic_file::write(&path, &bytes));
+    match stored {
+        Ok(()) => prune(dir),
+        Err(e) => eprintln!("Failed to cache cover: {}", e),
+    }
+    Ok(bytes)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, String> {
+    let response = ureq::get(url)
+        .timeout(REQUEST_TIMEOUT)
+        .call()
+        .map_err(|e| format!("Failed to download cover {}: {}", url, e))?;
+    read_capped(response.into_reader(), MAX_COVER_BYTES)
+        .map_err(|e| format!("Failed to download cover {}: {}", url, e))
+}
+
+// A body over the limit is an error rather than cut short, so a partial
+// image is never cached.
+fn read_capped(reader: impl Read, max_bytes: u64) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    reader.take(max_bytes + 1)
+        .read_to_end(&mut bytes)
+        .map_err(|e| e.to_string())?;
+    if bytes.len() as u64 > max_bytes {
+        return Err(format!("larger than {} bytes", max_bytes));
+    }
+    Ok(bytes)
+}
+
+fn touch(path: &Path) {
+    if let Ok(file) = std::fs::File::options().append(true).open(path) {
+        let _ = file.set_modified(std::time::SystemTime::now());
+    }
+}
+
+fn prune(dir: &Path) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    // Cached covers are named by hash alone; anything with an extension is
+    // a temp file still being written.
+    let mut covers: Vec<(std::time::SystemTime, PathBuf)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| !entry.file_name().to_string_lossy().contains('.'))
+        .filter_map(|entry| {
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    if covers.len() <= MAX_CACHED_COVERS {
+        return;
+    }
+    covers.sort();
+    for (_, path) in &covers[..covers.len() - MAX_CACHED_COVERS] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TempDir, TestServer};
+
+    #[test]
+    fn second_fetch_is_served_from_disk() {
+        let dir = TempDir::new("covers");
+        // The server answers once; a second download would fail.
+        let server = TestServer::start(vec![(200, "cover bytes")]);
+        let url = format!("{}/cover.jpg", server.url);
+
+        assert_eq!(fetch_in(dir.path(), &url).unwrap(), b"cover bytes");
+        assert_eq!(fetch_in(dir.path(), &url).unwrap(), b"cover bytes");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn failed_download_is_not_cached() {
+        let dir = TempDir::new("covers");
+        let server = TestServer::start(vec![(404, ""), (200, "cover bytes")]);
+        let url = format!("{}/cover.jpg", server.url);
+
+        assert!(fetch_in(dir.path(), &url).is_err());
+        assert_eq!(fetch_in(dir.path(), &url).unwrap(), b"cover bytes");
+    }
+
+    #[test]
+    fn oversized_body_is_an_error() {
+        assert_eq!(read_capped(&b"1234"[..], 4).unwrap(), b"1234");
+        assert_eq!(read_capped(&b"12345"[..], 4).unwrap_err(), "larger than 4 bytes");
+    }
+
+    #[test]
+    fn prune_keeps_the_most_recent_covers() {
+        let dir = TempDir::new("covers");
+        let start = std::time::SystemTime::now() - Duration::from_secs(3600);
+        for i in 0..MAX_CACHED_COVERS + 2 {
+            let path = dir.path().join(format!("cover{}", i));
+            std::fs::write(&path, b"x").unwrap();
+            let file = std::fs::File::options().append(true).open(&path).unwrap();
+            file.set_modified(start + Duration::from_secs(i as u64)).unwrap();
+        }
+
+        prune(dir.path());
+
+        assert!(!dir.path().join("cover0").exists());
+        assert!(!dir.path().join("cover1").exists());
+        assert!(dir.path().join("cover2").exists());
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), MAX_CACHED_COVERS);
+    }
+}
@@ -0,0 +1,521 @@
+use crate::now_playing::{self, NowPlaying, PlaybackState};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{Listener, Manager};
+
+// How often timers are checked while no reports arrive.
+const TICK: Duration = Duration::from_secs(5);
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(15);
+// The presence is cleared once playback has been paused this long.
+const PAUSE_CLEAR_AFTER: Duration = Duration::from_secs(60);
+// Discord rejects longer activity strings.
+const MAX_TEXT_CHARS: usize = 128;
+// Start times closer than this are the same playback, not a seek; this keeps
+// the heartbeat from running into Discord's activity rate limit.
+const TIMESTAMP_SLACK_MS: i64 = 2000;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DiscordSettings {
+    pub enabled: bool,
+    // Application ID from the Discord developer portal; its name is what
+    // Discord shows as "Listening to ...".
+    pub client_id: String,
+    // Only show that music is playing, not the track, artist or cover.
+    pub hide_track: bool,
+}
+
+impl Default for DiscordSettings {
+    fn default() -> Self {
+        DiscordSettings { enabled: false, client_id: String::new(), hide_track: false }
+    }
+}
+
+impl DiscordSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let client_id = self.client_id.trim();
+        if client_id.is_empty() || !client_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(format!("Discord application ID must be a number, got \"{}\"", self.client_id));
+        }
+        Ok(())
+    }
+}
+
+// Discord's local RPC framing: a little-endian opcode and payload length,
+// followed by that many bytes of JSON.
+pub mod ipc {
+    use super::*;
+
+    pub const OP_HANDSHAKE: u32 = 0;
+    pub const OP_FRAME: u32 = 1;
+    pub const OP_CLOSE: u32 = 2;
+    pub const OP_PING: u32 = 3;
+    pub const OP_PONG: u32 = 4;
+    // Real payloads are a few KiB; anything larger is a broken stream.
+    const MAX_PAYLOAD: usize = 1024 * 1024;
+
+    pub fn write_frame(writer: &mut impl Write, opcode: u32, payload: &Value) -> io::Result<()> {
+        let body = serde_json::to_vec(payload)?;
+        let mut frame = Vec::with_capacity(8 + body.len());
+        frame.extend_from_slice(&opcode.to_le_bytes());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&body);
+        writer.write_all(&frame)?;
+        writer.flush()
+    }
+
+    pub fn read_frame(reader: &mut impl Read) -> io::Result<(u32, Value)> {
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let opcode = u32::from_le_bytes([header[0], header[1], header[2], header[3]]);
+        let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+        if len > MAX_PAYLOAD {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Frame of {} bytes is too large", len)));
+        }
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body)?;
+        let payload = serde_json::from_slice(&body)?;
+        Ok((opcode, payload))
+    }
+}
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Stream = Pipe;
+
+// A named pipe opened as a file has no read timeout, so reads wait for data
+// with PeekNamedPipe and give up after TICK, like the Unix socket.
+#[cfg(windows)]
+struct Pipe(std::fs::File);
+
+#[cfg(windows)]
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use std::os::windows::io::AsRawHandle;
+        use windows::Win32::Foundation::HANDLE;
+        use windows::Win32::System::Pipes::PeekNamedPipe;
+
+        const POLL: Duration = Duration::from_millis(20);
+        let deadline = Instant::now() + TICK;
+        loop {
+            let mut available = 0u32;
+            unsafe { PeekNamedPipe(HANDLE(self.0.as_raw_handle()), None, 0, None, Some(&mut available), None) }
+                .map_err(io::Error::other)?;
+            if available > 0 {
+                let len = buf.len().min(available as usize);
+                return self.0.read(&mut buf[..len]);
+            }
+            if Instant::now() >= deadline {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, "Discord did not answer in time"));
+            }
+            std::thread::sleep(POLL);
+        }
+    }
+}
+
+#[cfg(windows)]
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+// Discord listens on the first free discord-ipc-0..9 socket in its runtime
+// directory; Flatpak and Snap builds put theirs in a subdirectory.
+#[cfg(unix)]
+fn open_stream() -> io::Result<Stream> {
+    let mut dirs: Vec<std::path::PathBuf> = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(Into::into)
+        .collect();
+    dirs.push("/tmp".into());
+
+    for dir in dirs {
+        for sub in ["", "app/com.discordapp.Discord", "snap.discord"] {
+            for n in 0..10 {
+                if let Ok(stream) = Stream::connect(dir.join(sub).join(format!("discord-ipc-{}", n))) {
+                    stream.set_read_timeout(Some(TICK))?;
+                    return Ok(stream);
+                }
+            }
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "Discord is not running"))
+}
+
+#[cfg(windows)]
+fn open_stream() -> io::Result<Stream> {
+    for n in 0..10 {
+        let path = format!(r"\\.\pipe\discord-ipc-{}", n);
+        if let Ok(pipe) = std::fs::OpenOptions::new().read(true).write(true).open(path) {
+            return Ok(Pipe(pipe));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::NotFound, "Discord is not running"))
+}
+
+struct Connection {
+    stream: Stream,
+    client_id: String,
+    nonce: u64,
+}
+
+impl Connection {
+    fn open(client_id: &str) -> io::Result<Self> {
+        Self::handshake(open_stream()?, client_id)
+    }
+
+    fn handshake(mut stream: Stream, client_id: &str) -> io::Result<Self> {
+        ipc::write_frame(&mut stream, ipc::OP_HANDSHAKE, &json!({ "v": 1, "client_id": client_id }))?;
+        let (opcode, payload) = ipc::read_frame(&mut stream)?;
+        if opcode == ipc::OP_CLOSE || payload.get("evt").and_then(Value::as_str) != Some("READY") {
+            let message = payload.get("message").and_then(Value::as_str).unwrap_or("handshake refused");
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, message.to_string()));
+        }
+        Ok(Connection { stream, client_id: client_id.to_string(), nonce: 0 })
+    }
+
+    // Sets or, with None, clears the activity and waits for Discord's answer.
+    fn set_activity(&mut self, activity: Option<&Value>) -> io::Result<()> {
+        self.nonce += 1;
+        let nonce = self.nonce.to_string();
+        let command = json!({
+            "cmd": "SET_ACTIVITY",
+            "args": { "pid": std::process::id(), "activity": activity },
+            "nonce": nonce,
+        });
+        ipc::write_frame(&mut self.stream, ipc::OP_FRAME, &command)?;
+
+        loop {
+            let (opcode, payload) = ipc::read_frame(&mut self.stream)?;
+            match opcode {
+                ipc::OP_PING => ipc::write_frame(&mut self.stream, ipc::OP_PONG, &payload)?,
+                ipc::OP_CLOSE => return Err(io::Error::new(io::ErrorKind::ConnectionAborted, "Discord closed the connection")),
+                _ if payload.get("nonce").and_then(Value::as_str) == Some(nonce.as_str()) => {
+                    // Discord refusing an activity is not a reason to reconnect.
+                    if payload.get("evt").and_then(Value::as_str) == Some("ERROR") {
+                        eprintln!("Discord rejected the activity: {}", payload.pointer("/data/message").unwrap_or(&payload));
+                    }
+                    return Ok(());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as i64).unwrap_or(0)
+}
+
+// Discord wants 2 to 128 characters.
+fn text(value: &str) -> String {
+    let mut text: String = value.chars().take(MAX_TEXT_CHARS).collect();
+    while text.chars().count() < 2 {
+        text.push(' ');
+    }
+    text
+}
+
+fn activity(np: &NowPlaying, settings: &DiscordSettings) -> Option<Value> {
+    let title = np.title.as_deref()?;
+    if np.state == PlaybackState::Stopped {
+        return None;
+    }
+
+    // Type 2 shows as "Listening to <application name>".
+    let mut activity = json!({ "type": 2 });
+    if settings.hide_track {
+        activity["details"] = json!("Listening to music");
+    } else {
+        activity["details"] = json!(text(title));
+        if let Some(artist) = &np.artist {
+            activity["state"] = json!(text(&format!("by {}", artist)));
+        }
+        if let Some(cover_url) = np.cover_url.as_deref().filter(|url| url.starts_with("https://")) {
+            let mut assets = json!({ "large_image": cover_url });
+            if let Some(album) = &np.album {
+                assets["large_text"] = json!(text(album));
+            }
+            activity["assets"] = assets;
+        }
+    }
+
+    match (np.state, now_playing::position_now(np)) {
+        (PlaybackState::Playing, Some(position)) => {
+            let start = unix_millis() - (position * 1000.0) as i64;
+            let mut timestamps = json!({ "start": start });
+            if let Some(duration) = np.duration {
+                timestamps["end"] = json!(start + (duration * 1000.0) as i64);
+            }
+            activity["timestamps"] = timestamps;
+        }
+        (PlaybackState::Paused, _) if !settings.hide_track => {
+            let state = match activity.get("state").and_then(Value::as_str) {
+                Some(state) => format!("{} (paused)", state),
+                None => "Paused".to_string(),
+            };
+            activity["state"] = json!(text(&state));
+        }
+        _ => {}
+    }
+    Some(activity)
+}
+
+// Whether two activities show the same thing, ignoring start times that only
+// drifted between reports.
+fn same_activity(a: Option<&Value>, b: Option<&Value>) -> bool {
+    let (Some(a), Some(b)) = (a, b) else {
+        return a.is_none() && b.is_none();
+    };
+    let without_timestamps = |v: &Value| {
+        let mut v = v.clone();
+        if let Some(map) = v.as_object_mut() {
+            map.remove("timestamps");
+        }
+        v
+    };
+    let start = |v: &Value| v.pointer("/timestamps/start").and_then(Value::as_i64);
+    let starts_match = match (start(a), start(b)) {
+        (Some(a), Some(b)) => (a - b).abs() <= TIMESTAMP_SLACK_MS,
+        (a, b) => a == b,
+    };
+    starts_match && without_timestamps(a) == without_timestamps(b)
+}
+
+enum Message {
+    NowPlaying(NowPlaying),
+    SettingsChanged,
+}
+
+struct Presence {
+    app: tauri::AppHandle,
+    np: NowPlaying,
+    connection: Option<Connection>,
+    last_attempt: Option<Instant>,
+    // What Discord currently shows; None until something has been sent on
+    // this connection.
+    shown: Option<Option<Value>>,
+    paused_since: Option<Instant>,
+}
+
+impl Presence {
+    fn settings(&self) -> DiscordSettings {
+        let state = self.app.state::<crate::AppState>();
+        state.settings.lock().map(|s| s.discord.clone()).unwrap_or_default()
+    }
+
+    fn disconnect(&mut self) {
+        // Closing the socket is enough for Discord to drop the activity.
+        self.connection = None;
+        self.shown = None;
+    }
+
+    fn connection(&mut self, client_id: &str) -> Option<&mut Connection> {
+        if self.connection.as_ref().is_some_and(|c| c.client_id != client_id) {
+            self.disconnect();
+        }
+        if self.connection.is_none() {
+            if self.last_attempt.is_some_and(|at| at.elapsed() < RECONNECT_INTERVAL) {
+                return None;
+            }
+            self.last_attempt = Some(Instant::now());
+            match Connection::open(client_id) {
+                Ok(connection) => self.connection = Some(connection),
+                // Discord simply not running is the normal case.
+                Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+                Err(e) => {
+                    eprintln!("Failed to connect to Discord: {}", e);
+                    return None;
+                }
+            }
+        }
+        self.connection.as_mut()
+    }
+
+    fn sync(&mut self) {
+        let settings = self.settings();
+        if !settings.enabled || settings.validate().is_err() {
+            self.disconnect();
+            return;
+        }
+
+        if self.np.state == PlaybackState::Paused {
+            self.paused_since.get_or_insert_with(Instant::now);
+        } else {
+            self.paused_since = None;
+        }
+        let wanted = match self.paused_since {
+            Some(since) if since.elapsed() >= PAUSE_CLEAR_AFTER => None,
+            _ => activity(&self.np, &settings),
+        };
+        // Nothing to show is not worth starting a connection for.
+        if wanted.is_none() && self.connection.is_none() {
+            return;
+        }
+        if let Some(shown) = &self.shown
+            && same_activity(shown.as_ref(), wanted.as_ref()) {
+                return;
+            }
+
+        let Some(connection) = self.connection(settings.client_id.trim()) else {
+            return;
+        };
+        match connection.set_activity(wanted.as_ref()) {
+            Ok(()) => self.shown = Some(wanted),
+            Err(e) => {
+                eprintln!("Lost connection to Discord: {}", e);
+                self.disconnect();
+            }
+        }
+    }
+
+    fn run(mut self, messages: mpsc::Receiver<Message>) {
+        loop {
+            match messages.recv_timeout(TICK) {
+                Ok(Message::NowPlaying(np)) => self.np = np,
+                Ok(Message::SettingsChanged) => self.shown = None,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            self.sync();
+        }
+    }
+}
+
+// Publishes the now-playing state as Discord Rich Presence from a thread of
+// its own, reconnecting whenever Discord is restarted.
+pub fn start(app: tauri::AppHandle) {
+    let (sender, messages) = mpsc::channel();
+
+    let now_playing_sender = sender.clone();
+    app.listen(now_playing::CHANGED_EVENT, move |event| {
+        if let Ok(np) = serde_json::from_str::<NowPlaying>(event.payload()) {
+            let _ = now_playing_sender.send(Message::NowPlaying(np));
+        }
+    });
+    app.listen("settings-changed", move |_| {
+        let _ = sender.send(Message::SettingsChanged);
+    });
+
+    let presence = Presence {
+        app,
+        np: NowPlaying::default(),
+        connection: None,
+        last_attempt: None,
+        shown: None,
+        paused_since: None,
+    };
+    std::thread::spawn(move || presence.run(messages));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buffer = Vec::new();
+        ipc::write_frame(&mut buffer, ipc::OP_HANDSHAKE, &json!({ "v": 1, "client_id": "123" })).unwrap();
+        ipc::write_frame(&mut buffer, ipc::OP_FRAME, &json!({ "cmd": "SET_ACTIVITY", "nonce": "1" })).unwrap();
+
+        let body_len = serde_json::to_vec(&json!({ "v": 1, "client_id": "123" })).unwrap().len() as u32;
+        assert_eq!(buffer[..4], ipc::OP_HANDSHAKE.to_le_bytes());
+        assert_eq!(buffer[4..8], body_len.to_le_bytes());
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(ipc::read_frame(&mut reader).unwrap(), (ipc::OP_HANDSHAKE, json!({ "v": 1, "client_id": "123" })));
+        assert_eq!(ipc::read_frame(&mut reader).unwrap(), (ipc::OP_FRAME, json!({ "cmd": "SET_ACTIVITY", "nonce": "1" })));
+        assert_eq!(ipc::read_frame(&mut reader).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn truncated_and_oversized_frames_are_errors() {
+        let mut buffer = Vec::new();
+        ipc::write_frame(&mut buffer, ipc::OP_FRAME, &json!({ "evt": "READY" })).unwrap();
+        buffer.pop();
+        let error = ipc::read_frame(&mut Cursor::new(buffer)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut header = ipc::OP_FRAME.to_le_bytes().to_vec();
+        header.extend_from_slice(&u32::MAX.to_le_bytes());
+        let error = ipc::read_frame(&mut Cursor::new(header)).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    // Plays Discord's side of the socket on another thread.
+    #[cfg(unix)]
+    fn fake_discord(script: impl FnOnce(&mut Stream) + Send + 'static) -> (Stream, std::thread::JoinHandle<()>) {
+        let (client, mut server) = Stream::pair().unwrap();
+        client.set_read_timeout(Some(TICK)).unwrap();
+        let thread = std::thread::spawn(move || script(&mut server));
+        (client, thread)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn handshake_and_set_activity_over_a_socket() {
+        let (stream, discord) = fake_discord(|socket| {
+            let (opcode, handshake) = ipc::read_frame(socket).unwrap();
+            assert_eq!(opcode, ipc::OP_HANDSHAKE);
+            assert_eq!(handshake, json!({ "v": 1, "client_id": "123" }));
+            ipc::write_frame(socket, ipc::OP_FRAME, &json!({ "cmd": "DISPATCH", "evt": "READY" })).unwrap();
+
+            let (opcode, command) = ipc::read_frame(socket).unwrap();
+            assert_eq!(opcode, ipc::OP_FRAME);
+            assert_eq!(command["cmd"], "SET_ACTIVITY");
+            assert_eq!(command["args"]["activity"]["details"], "Song");
+
+            // A ping in the middle of the exchange has to be answered first.
+            ipc::write_frame(socket, ipc::OP_PING, &json!({ "n": 1 })).unwrap();
+            assert_eq!(ipc::read_frame(socket).unwrap(), (ipc::OP_PONG, json!({ "n": 1 })));
+            ipc::write_frame(socket, ipc::OP_FRAME, &json!({ "cmd": "SET_ACTIVITY", "nonce": "other" })).unwrap();
+            ipc::write_frame(socket, ipc::OP_FRAME, &json!({ "cmd": "SET_ACTIVITY", "nonce": command["nonce"] })).unwrap();
+        });
+
+        let mut connection = Connection::handshake(stream, "123").unwrap();
+        connection.set_activity(Some(&json!({ "type": 2, "details": "Song" }))).unwrap();
+        discord.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn refused_handshake_is_an_error() {
+        let (stream, discord) = fake_discord(|socket| {
+            ipc::read_frame(socket).unwrap();
+            ipc::write_frame(socket, ipc::OP_CLOSE, &json!({ "code": 4000, "message": "Invalid Client ID" })).unwrap();
+        });
+
+        let error = Connection::handshake(stream, "0").err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::ConnectionRefused);
+        assert_eq!(error.to_string(), "Invalid Client ID");
+        discord.join().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn silent_discord_times_out() {
+        let (stream, discord) = fake_discord(|socket| {
+            ipc::read_frame(socket).unwrap();
+            // Keep the socket open without answering.
+            std::thread::sleep(TICK + Duration::from_secs(1));
+        });
+        let started = Instant::now();
+        assert!(Connection::handshake(stream, "123").is_err());
+        assert!(started.elapsed() < TICK + Duration::from_secs(1));
+        discord.join().unwrap();
+    }
+}
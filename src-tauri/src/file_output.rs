@@ -0,0 +1,165 @@
+use crate::now_playing::{self, NowPlaying, PlaybackState};
+use crate::template::Template;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use tauri::{Listener, Manager};
+
+// Files kept up to date with the current track, e.g. for OBS text and image
+// sources.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FileOutput {
+    pub files: Vec<OutputFile>,
+    // Where to keep a copy of the current cover image.
+    pub cover_path: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OutputFile {
+    pub path: String,
+    // Without a template the file gets the full now-playing state as JSON.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+fn check_path(path: &str) -> Result<(), String> {
+    if path.trim().is_empty() {
+        return Err("Now-playing output file path is empty".to_string());
+    }
+    if !Path::new(path).is_absolute() {
+        return Err(format!("Now-playing output file path must be absolute: {}", path));
+    }
+    Ok(())
+}
+
+impl FileOutput {
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen = Vec::new();
+        for file in &self.files {
+            check_path(&file.path)?;
+            if let Some(template) = &file.template {
+                Template::parse(template)?;
+            }
+            if seen.contains(&&file.path) {
+                return Err(format!("{} is listed as a now-playing output file twice", file.path));
+            }
+            seen.push(&file.path);
+        }
+        if let Some(cover_path) = &self.cover_path {
+            check_path(cover_path)?;
+            if seen.contains(&cover_path) {
+                return Err(format!("{} is used for both text and the cover image", cover_path));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn render(file: &OutputFile, np: &NowPlaying) -> Result<String, String> {
+    let position = now_playing::position_now(np);
+    match &file.template {
+        Some(template) => Ok(Template::parse(template)?.render(np, position)),
+        None => {
+            let np = NowPlaying { position, ..np.clone() };
+            serde_json::to_string_pretty(&np).map_err(|e| format!("Failed to serialize now playing: {}", e))
+        }
+    }
+}
+
+enum Message {
+    NowPlaying(NowPlaying),
+    SettingsChanged,
+}
+
+#[derive(Default)]
+struct Writer {
+    // What each file was last written with, so the heartbeat only touches
+    // files whose contents changed.
+    written: HashMap<PathBuf, String>,
+    cover_url: Option<String>,
+}
+
+impl Writer {
+    fn write(&mut self, path: &Path, contents: String) {
+        if self.written.get(path) == Some(&contents) {
+            return;
+        }
+        match crate::atomic_file::write(path, contents.as_bytes()) {
+            Ok(()) => {
+                self.written.insert(path.to_path_buf(), contents);
+            }
+            Err(e) => eprintln!("Failed to write now-playing file: {}", e),
+        }
+    }
+
+    fn update_cover(&mut self, path: &Path, cover_url: Option<&String>) {
+        if self.cover_url.as_ref() == cover_url {
+            return;
+        }
+        let result = match cover_url {
+            Some(url) => crate::covers::fetch(url).and_then(|bytes| crate::atomic_file::write(path, &bytes)),
+            // Nothing playing; an image source without a file shows nothing.
+            None if path.exists() => std::fs::remove_file(path)
+                .map_err(|e| format!("Failed to remove {}: {}", path.display(), e)),
+            None => Ok(()),
+        };
+        // Only a cover that made it to disk counts, so a failed download is
+        // tried again on the next update.
+        match result {
+            Ok(()) => self.cover_url = cover_url.cloned(),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    fn update(&mut self, output: &FileOutput, np: &NowPlaying) {
+        let stopped = np.state == PlaybackState::Stopped || np.title.is_none();
+        for file in &output.files {
+            let contents = if stopped {
+                Ok(String::new())
+            } else {
+                render(file, np)
+            };
+            match contents {
+                Ok(contents) => self.write(Path::new(&file.path), contents),
+                Err(e) => eprintln!("Failed to render {}: {}", file.path, e),
+            }
+        }
+        if let Some(cover_path) = &output.cover_path {
+            let cover_url = np.cover_url.as_ref().filter(|url| !stopped && url.starts_with("https://"));
+            self.update_cover(Path::new(cover_path), cover_url);
+        }
+    }
+}
+
+// Writes on a thread of its own so slow disks and cover downloads never hold
+// up the UI.
+pub fn start(app: tauri::AppHandle) {
+    let (sender, messages) = mpsc::channel();
+
+    let now_playing_sender = sender.clone();
+    app.listen(now_playing::CHANGED_EVENT, move |event| {
+        if let Ok(np) = serde_json::from_str::<NowPlaying>(event.payload()) {
+            let _ = now_playing_sender.send(Message::NowPlaying(np));
+        }
+    });
+    app.listen("settings-changed", move |_| {
+        let _ = sender.send(Message::SettingsChanged);
+    });
+
+    std::thread::spawn(move || {
+        let mut writer = Writer::default();
+        let mut np = NowPlaying::default();
+        for message in messages {
+            match message {
+                Message::NowPlaying(update) => np = update,
+                // Files may have been added or moved; write them all again.
+                Message::SettingsChanged => writer = Writer::default(),
+            }
+            let state = app.state::<crate::AppState>();
+            let output = state.settings.lock().map(|s| s.file_output.clone()).unwrap_or_default();
+            writer.update(&output, &np);
+        }
+    });
+}
@@ -0,0 +1,392 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::Manager;
+
+// Size of the main window in mini player mode.
+const MINI_PLAYER_SIZE: tauri::LogicalSize<f64> = tauri::LogicalSize { width: 420.0, height: 180.0 };
+
+// Window geometry to restore when leaving the mini player.
+static MINI_PLAYER_RESTORE: Mutex<Option<(tauri::PhysicalPosition<i32>, tauri::PhysicalSize<u32>)>> = Mutex::new(None);
+
+// App-level keys handled inside the main window, on top of the ones the
+// Qobuz page handles itself. An empty string disables a key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keymap {
+    pub open_settings: String,
+    pub focus_search: String,
+    pub mini_player: String,
+    pub fullscreen: String,
+    pub reload: String,
+    pub cheat_sheet: String,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap {
+            open_settings: "Ctrl+,".to_string(),
+            focus_search: "Ctrl+L".to_string(),
+            mini_player: "Ctrl+Shift+M".to_string(),
+            fullscreen: "F11".to_string(),
+            reload: "Ctrl+R".to_string(),
+            cheat_sheet: "?".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AppAction {
+    OpenSettings,
+    FocusSearch,
+    MiniPlayer,
+    Fullscreen,
+    Reload,
+    CheatSheet,
+}
+
+impl AppAction {
+    const ALL: [AppAction; 6] = [
+        AppAction::OpenSettings,
+        AppAction::FocusSearch,
+        AppAction::MiniPlayer,
+        AppAction::Fullscreen,
+        AppAction::Reload,
+        AppAction::CheatSheet,
+    ];
+
+    pub fn id(&self) -> &'static str {
+        match self {
+            AppAction::OpenSettings => "open_settings",
+            AppAction::FocusSearch => "focus_search",
+            AppAction::MiniPlayer => "mini_player",
+            AppAction::Fullscreen => "fullscreen",
+            AppAction::Reload => "reload",
+            AppAction::CheatSheet => "cheat_sheet",
+        }
+    }
+
+    pub fn from_id(id: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|action| action.id() == id)
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            AppAction::OpenSettings => "Open settings",
+            AppAction::FocusSearch => "Search",
+            AppAction::MiniPlayer => "Toggle the mini player",
+            AppAction::Fullscreen => "Toggle full screen",
+            AppAction::Reload => "Reload the player",
+            AppAction::CheatSheet => "Show this list",
+        }
+    }
+}
+
+// One row of the keymap as the page sees it: the listener matches on `keys`
+// and the cheat sheet lists `keys` and `description`.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeymapEntry {
+    pub action: &'static str,
+    pub keys: String,
+    pub description: &'static str,
+}
+
+impl Keymap {
+    fn keys(&self, action: AppAction) -> &str {
+        match action {
+            AppAction::OpenSettings => &self.open_settings,
+            AppAction::FocusSearch => &self.focus_search,
+            AppAction::MiniPlayer => &self.mini_player,
+            AppAction::Fullscreen => &self.fullscreen,
+            AppAction::Reload => &self.reload,
+            AppAction::CheatSheet => &self.cheat_sheet,
+        }
+    }
+
+    pub fn entries(&self) -> Vec<KeymapEntry> {
+        AppAction::ALL.into_iter()
+            .filter(|action| !self.keys(*action).trim().is_empty())
+            .map(|action| KeymapEntry {
+                action: action.id(),
+                keys: self.keys(action).trim().to_string(),
+                description: action.description(),
+            })
+            .collect()
+    }
+
+    // Keys are modifiers (Ctrl, Alt, Shift, Super) joined with "+" and ending
+    // in a key name as reported by KeyboardEvent.key, e.g. "Ctrl+Shift+M".
+    pub fn validate(&self) -> Result<(), String> {
+        let mut seen: Vec<(String, AppAction)> = Vec::new();
+        for action in AppAction::ALL {
+            let keys = self.keys(action).trim();
+            if keys.is_empty() {
+                continue;
+            }
+            let normalized = normalize(keys)
+                .map_err(|e| format!("Invalid key \"{}\" for {}: {}", keys, action.id(), e))?;
+            if let Some((_, other)) = seen.iter().find(|(n, _)| *n == normalized) {
+                return Err(format!("\"{}\" is assigned to both {} and {}", keys, other.id(), action.id()));
+            }
+            seen.push((normalized, action));
+        }
+        Ok(())
+    }
+}
+
+fn normalize(keys: &str) -> Result<String, String> {
+    // "+" is a key in its own right, on its own or as in "Ctrl++". The
+    // parse() in LISTENER_JS splits keys the same way.
+    let (modifiers, key) = if keys == "+" {
+        ("", "+")
+    } else if let Some(rest) = keys.strip_suffix("++") {
+        (rest, "+")
+    } else {
+        keys.rsplit_once('+').unwrap_or(("", keys))
+    };
+    if key.is_empty() {
+        return Err("missing key".to_string());
+    }
+
+    let mut flags = [false; 4];
+    for modifier in modifiers.split('+').filter(|m| !m.is_empty()) {
+        let index = match modifier.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => 0,
+            "alt" => 1,
+            "shift" => 2,
+            "super" | "meta" | "cmd" => 3,
+            other => return Err(format!("unknown modifier \"{}\"", other)),
+        };
+        flags[index] = true;
+    }
+    Ok(format!("{:?}{}", flags, key.to_lowercase()))
+}
+
+// Installed on every page the main window loads. Reads the keymap from Rust
+// on load and after every settings change, so the keys that work and the
+// ones the cheat sheet and titlebar tooltips show always come from the same
+// data.
+pub const LISTENER_JS: &str = r#"
+(function() {
+    if (window.__qobuzKeymapListener) return;
+    window.__qobuzKeymapListener = true;
+    window.__qobuzKeymap = [];
+
+    const invoke = (cmd, args) => window.__TAURI__ && window.__TAURI__.core
+        ? window.__TAURI__.core.invoke(cmd, args)
+        : Promise.reject('Tauri API not available');
+
+    // Splits keys the same way as normalize() in keymap.rs.
+    function parse(keys) {
+        let key = keys;
+        let modifiers = '';
+        if (keys === '+') {
+            key = '+';
+        } else if (keys.endsWith('++')) {
+            key = '+';
+            modifiers = keys.slice(0, -2);
+        } else if (keys.includes('+')) {
+            key = keys.slice(keys.lastIndexOf('+') + 1);
+            modifiers = keys.slice(0, keys.lastIndexOf('+'));
+        }
+        const mods = modifiers.toLowerCase().split('+');
+        return {
+            ctrl: mods.includes('ctrl') || mods.includes('control'),
+            alt: mods.includes('alt'),
+            shift: mods.includes('shift'),
+            meta: mods.includes('super') || mods.includes('meta') || mods.includes('cmd'),
+            key: key.toLowerCase()
+        };
+    }
+
+    function matches(binding, event) {
+        if (binding.key !== event.key.toLowerCase()) return false;
+        if (binding.ctrl !== event.ctrlKey || binding.alt !== event.altKey || binding.meta !== event.metaKey) return false;
+        // Symbols such as "?" already need Shift on most layouts.
+        const symbol = binding.key.length === 1 && binding.key.toLowerCase() === binding.key.toUpperCase();
+        return symbol || binding.shift === event.shiftKey;
+    }
+
+    function isTyping(target) {
+        return target && (target.isContentEditable || ['INPUT', 'TEXTAREA', 'SELECT'].includes(target.tagName));
+    }
+
+    function applyTooltips() {
+        document.querySelectorAll('[data-keymap-action]').forEach(el => {
+            const entry = window.__qobuzKeymap.find(e => e.action === el.dataset.keymapAction);
+            const base = el.dataset.keymapTitle || el.title;
+            el.dataset.keymapTitle = base;
+            el.title = entry ? base + ' (' + entry.keys + ')' : base;
+        });
+    }
+
+    function refresh() {
+        invoke('get_keymap').then(entries => {
+            window.__qobuzKeymap = entries;
+            applyTooltips();
+        }).catch(() => {});
+    }
+    window.__qobuzRefreshKeymap = refresh;
+
+    function toggleCheatSheet() {
+        const existing = document.getElementById('qobuz-cheat-sheet');
+        if (existing) {
+            existing.remove();
+            return;
+        }
+
+        const overlay = document.createElement('div');
+        overlay.id = 'qobuz-cheat-sheet';
+        overlay.style.cssText = 'position:fixed;inset:0;z-index:2147483646;display:flex;align-items:center;justify-content:center;background:rgba(0,0,0,0.6);font-family:-apple-system,BlinkMacSystemFont,"Segoe UI",Roboto,sans-serif;';
+        const panel = document.createElement('div');
+        panel.style.cssText = 'background:#202020;color:#e0e0e0;border-radius:8px;padding:20px 24px;min-width:320px;box-shadow:0 8px 32px rgba(0,0,0,0.5);';
+        const title = document.createElement('div');
+        title.textContent = 'Keyboard Shortcuts';
+        title.style.cssText = 'font-size:16px;font-weight:600;margin-bottom:12px;';
+        panel.appendChild(title);
+
+        window.__qobuzKeymap.forEach(entry => {
+            const row = document.createElement('div');
+            row.style.cssText = 'display:flex;justify-content:space-between;gap:24px;padding:4px 0;font-size:13px;';
+            const description = document.createElement('span');
+            description.textContent = entry.description;
+            const keys = document.createElement('kbd');
+            keys.textContent = entry.keys;
+            keys.style.cssText = 'font-family:Consolas,"Courier New",monospace;background:#333;border-radius:4px;padding:1px 6px;';
+            row.appendChild(description);
+            row.appendChild(keys);
+            panel.appendChild(row);
+        });
+
+        overlay.appendChild(panel);
+        overlay.addEventListener('click', () => overlay.remove());
+        document.body.appendChild(overlay);
+    }
+
+    function focusSearch() {
+        const selectors = ['input[type="search"]', 'input[placeholder*="earch"]', '.search-input input', '[data-testid*="search"] input'];
+        for (const s of selectors) {
+            const el = document.querySelector(s);
+            if (el) { el.focus(); el.select && el.select(); return; }
+        }
+    }
+
+    document.addEventListener('keydown', event => {
+        if (event.key === 'Escape') {
+            const sheet = document.getElementById('qobuz-cheat-sheet');
+            if (sheet) { sheet.remove(); return; }
+        }
+
+        const entry = window.__qobuzKeymap.find(e => matches(parse(e.keys), event));
+        if (!entry) return;
+        // Plain keys still type into text fields.
+        const plain = !event.ctrlKey && !event.altKey && !event.metaKey && !/^F\d{1,2}$/.test(event.key);
+        if (plain && isTyping(event.target)) return;
+
+        event.preventDefault();
+        event.stopPropagation();
+        if (entry.action === 'cheat_sheet') {
+            toggleCheatSheet();
+        } else if (entry.action === 'focus_search') {
+            focusSearch();
+        } else {
+            invoke('run_app_action', { action: entry.action }).catch(e => console.error(e));
+        }
+    }, true);
+
+    if (document.readyState === 'loading') {
+        document.addEventListener('DOMContentLoaded', refresh);
+    } else {
+        refresh();
+    }
+    window.addEventListener('load', () => setTimeout(applyTooltips, 1500));
+    if (window.__TAURI__ && window.__TAURI__.event) {
+        window.__TAURI__.event.listen('settings-changed', refresh);
+    }
+})();
+"#;
+
+// Actions that need the native window. Search and the cheat sheet are
+// handled by the page script.
+pub fn run(app: &tauri::AppHandle, action: AppAction) -> Result<(), String> {
+    let window = app.get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+
+    match action {
+        AppAction::OpenSettings => crate::open_settings_window(app.clone()),
+        AppAction::MiniPlayer => toggle_mini_player(&window),
+        AppAction::Fullscreen => {
+            let fullscreen = window.is_fullscreen()
+                .map_err(|e| format!("Failed to read window state: {}", e))?;
+            window.set_fullscreen(!fullscreen)
+                .map_err(|e| format!("Failed to toggle full screen: {}", e))
+        }
+        AppAction::Reload => window.reload().map_err(|e| format!("Failed to reload: {}", e)),
+        AppAction::FocusSearch | AppAction::CheatSheet => Ok(()),
+    }
+}
+
+// The minimum size from tauri.conf.json, which the mini player has to lift.
+fn configured_min_size(window: &tauri::WebviewWindow) -> Option<tauri::LogicalSize<f64>> {
+    let config = window.app_handle().config();
+    let main = config.app.windows.iter().find(|w| w.label == window.label())?;
+    match (main.min_width, main.min_height) {
+        (Some(width), Some(height)) => Some(tauri::LogicalSize { width, height }),
+        _ => None,
+    }
+}
+
+fn toggle_mini_player(window: &tauri::WebviewWindow) -> Result<(), String> {
+    let mut restore = MINI_PLAYER_RESTORE.lock()
+        .map_err(|e| format!("Failed to lock window state: {}", e))?;
+
+    if let Some((position, size)) = restore.take() {
+        let _ = window.set_always_on_top(false);
+        window.set_size(size).map_err(|e| format!("Failed to resize window: {}", e))?;
+        window.set_min_size(configured_min_size(window))
+            .map_err(|e| format!("Failed to set minimum window size: {}", e))?;
+        return window.set_position(position).map_err(|e| format!("Failed to move window: {}", e));
+    }
+
+    let position = window.outer_position().map_err(|e| format!("Failed to read window position: {}", e))?;
+    let size = window.inner_size().map_err(|e| format!("Failed to read window size: {}", e))?;
+    let _ = window.unmaximize();
+    let _ = window.set_fullscreen(false);
+    // Otherwise the minimum size clamps the window back to full size.
+    window.set_min_size(None::<tauri::Size>)
+        .map_err(|e| format!("Failed to set minimum window size: {}", e))?;
+    window.set_size(MINI_PLAYER_SIZE).map_err(|e| format!("Failed to resize window: {}", e))?;
+    let _ = window.set_always_on_top(true);
+    *restore = Some((position, size));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plus_is_a_key() {
+        assert_eq!(normalize("Ctrl++").unwrap(), format!("{:?}+", [true, false, false, false]));
+        assert_eq!(normalize("Ctrl+Shift++").unwrap(), format!("{:?}+", [true, false, true, false]));
+        assert_eq!(normalize("+").unwrap(), format!("{:?}+", [false; 4]));
+        assert_ne!(normalize("+").unwrap(), normalize("Ctrl++").unwrap());
+        assert!(normalize("Ctrl+").is_err());
+    }
+
+    #[test]
+    fn modifiers_are_case_insensitive_and_aliased() {
+        assert_eq!(normalize("ctrl+shift+m").unwrap(), normalize("Control+Shift+M").unwrap());
+        assert_eq!(normalize("Cmd+K").unwrap(), normalize("Super+K").unwrap());
+        assert!(normalize("Hyper+K").is_err());
+    }
+
+    #[test]
+    fn validate_rejects_duplicate_keys() {
+        let keymap = Keymap { reload: "+".to_string(), fullscreen: "Ctrl++".to_string(), ..Keymap::default() };
+        assert!(keymap.validate().is_ok());
+
+        let keymap = Keymap { reload: "ctrl+l".to_string(), ..Keymap::default() };
+        let error = keymap.validate().unwrap_err();
+        assert!(error.contains("focus_search") && error.contains("reload"), "{}", error);
+    }
+}
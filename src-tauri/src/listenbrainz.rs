@@ -0,0 +1,212 @@
+use crate::listens::{self, Listen, ListenService, SubmitError};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use std::time::Duration;
+
+pub const DEFAULT_API_URL: &str = "https://api.listenbrainz.org";
+const QUEUE_FILE: &str = "listenbrainz-queue.json";
+// The server takes up to 1000 listens per import; smaller batches keep each
+// request well under its size limit.
+const BATCH_SIZE: usize = 100;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+// The base URL can point at a self-hosted ListenBrainz server.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListenBrainzSettings {
+    pub enabled: bool,
+    pub api_url: String,
+    pub token: String,
+}
+
+impl Default for ListenBrainzSettings {
+    fn default() -> Self {
+        ListenBrainzSettings {
+            enabled: false,
+            api_url: DEFAULT_API_URL.to_string(),
+            token: String::new(),
+        }
+    }
+}
+
+impl ListenBrainzSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        if !self.api_url.starts_with("https://") && !self.api_url.starts_with("http://") {
+            return Err(format!("ListenBrainz URL must start with http:// or https://, got \"{}\"", self.api_url));
+        }
+        if self.token.trim().is_empty() {
+            return Err("ListenBrainz is enabled but token is empty".to_string());
+        }
+        Ok(())
+    }
+}
+
+struct Client {
+    settings: ListenBrainzSettings,
+}
+
+fn track_metadata(listen: &Listen) -> Value {
+    let mut additional_info = json!({
+        "media_player": "Qobuz Player",
+        "submission_client": "Qobuz Player",
+        "submission_client_version": env!("CARGO_PKG_VERSION"),
+        "music_service": "qobuz.com",
+    });
+    if let Some(duration) = listen.duration {
+        additional_info["duration_ms"] = json!((duration * 1000.0).round() as u64);
+    }
+
+    let mut metadata = json!({
+        "artist_name": listen.artist,
+        "track_name": listen.title,
+        "additional_info": additional_info,
+    });
+    if let Some(album) = &listen.album {
+        metadata["release_name"] = json!(album);
+    }
+    metadata
+}
+
+impl Client {
+    fn submit_listens(&self, listen_type: &str, payload: Vec<Value>) -> Result<(), SubmitError> {
+        let url = format!("{}/1/submit-listens", self.settings.api_url.trim_end_matches('/'));
+        let body = json!({ "listen_type": listen_type, "payload": payload });
+        let result = ureq::post(&url)
+            .timeout(REQUEST_TIMEOUT)
+            .set("Authorization", &format!("Token {}", self.settings.token.trim()))
+            .send_json(body);
+
+        match result {
+            Ok(_) => Ok(()),
+            Err(ureq::Error::Status(status, response)) => {
+                let message = response.into_json::<Value>().ok()
+                    .and_then(|body| body.get("error").and_then(Value::as_str).map(str::to_string))
+                    .unwrap_or_else(|| format!("HTTP {}", status));
+                let message = format!("Submitting {} listens failed: {}", listen_type, message);
+                Err(match status {
+                    // A bad token keeps the queue until the settings are
+                    // fixed.
+                    401 => SubmitError::Session(format!("{}; check the token in the settings", message)),
+                    429 => SubmitError::Retry(message),
+                    status if status >= 500 => SubmitError::Retry(message),
+                    _ => SubmitError::Rejected(message),
+                })
+            }
+            Err(e) => Err(SubmitError::Retry(format!("Failed to reach {}: {}", url, e))),
+        }
+    }
+}
+
+impl ListenService for Client {
+    fn now_playing(&mut self, listen: &Listen) -> Result<(), SubmitError> {
+        self.submit_listens("playing_now", vec![json!({ "track_metadata": track_metadata(listen) })])
+    }
+
+    // A single fresh listen goes in as "single"; backlog from the queue is
+    // sent as "import".
+    fn submit(&mut self, listens: &[Listen]) -> Result<(), SubmitError> {
+        let listen_type = if listens.len() == 1 { "single" } else { "import" };
+        let payload = listens.iter()
+            .map(|listen| json!({
+                "listened_at": listen.started_at,
+                "track_metadata": track_metadata(listen),
+            }))
+            .collect();
+        self.submit_listens(listen_type, payload)
+    }
+}
+
+pub fn start(app: tauri::AppHandle) {
+    listens::start(app, listens::Service {
+        name: "ListenBrainz",
+        queue_file: QUEUE_FILE,
+        batch_size: BATCH_SIZE,
+        settings: |settings| Some(settings.listenbrainz.clone())
+            .filter(|listenbrainz| listenbrainz.enabled && listenbrainz.validate().is_ok()),
+        connect: |settings| Box::new(Client { settings }),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{TestServer, listen};
+
+    fn client(api_url: &str) -> Client {
+        Client {
+            settings: ListenBrainzSettings {
+                enabled: true,
+                api_url: api_url.to_string(),
+                token: " user-token ".to_string(),
+            },
+        }
+    }
+
+    #[test]
+    fn now_playing_is_sent_without_a_timestamp() {
+        let server = TestServer::start(vec![(200, r#"{"status":"ok"}"#)]);
+        client(&format!("{}/", server.url)).now_playing(&listen("One", 1000)).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/1/submit-listens");
+        assert_eq!(requests[0].header("authorization"), Some("Token user-token"));
+
+        let body = requests[0].json();
+        assert_eq!(body["listen_type"], "playing_now");
+        let payload = body["payload"].as_array().unwrap();
+        assert_eq!(payload.len(), 1);
+        assert!(payload[0].get("listened_at").is_none());
+        let metadata = &payload[0]["track_metadata"];
+        assert_eq!(metadata["artist_name"], "Artist");
+        assert_eq!(metadata["track_name"], "One");
+        assert_eq!(metadata["release_name"], "Album");
+        assert_eq!(metadata["additional_info"]["duration_ms"], 200400);
+        assert_eq!(metadata["additional_info"]["submission_client"], "Qobuz Player");
+    }
+
+    #[test]
+    fn one_listen_is_single_and_a_backlog_is_an_import() {
+        let server = TestServer::start(vec![(200, r#"{"status":"ok"}"#), (200, r#"{"status":"ok"}"#)]);
+        let mut client = client(&server.url);
+        client.submit(&[listen("One", 1000)]).unwrap();
+        client.submit(&[listen("Two", 2000), listen("Three", 2300)]).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let single = requests[0].json();
+        assert_eq!(single["listen_type"], "single");
+        assert_eq!(single["payload"][0]["listened_at"], 1000);
+
+        let import = requests[1].json();
+        assert_eq!(import["listen_type"], "import");
+        let payload = import["payload"].as_array().unwrap();
+        assert_eq!(payload.len(), 2);
+        assert_eq!(payload[0]["listened_at"], 2000);
+        assert_eq!(payload[1]["track_metadata"]["track_name"], "Three");
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        let server = TestServer::start(vec![
+            (401, r#"{"code":401,"error":"Invalid authorization token."}"#),
+            (429, r#"{"code":429,"error":"Too many requests"}"#),
+            (503, "Service Unavailable"),
+            (400, r#"{"code":400,"error":"Invalid JSON document submitted."}"#),
+        ]);
+        let mut client = client(&server.url);
+        let batch = [listen("One", 1000)];
+
+        match client.submit(&batch) {
+            Err(SubmitError::Session(e)) => assert!(e.contains("Invalid authorization token."), "{}", e),
+            other => panic!("expected a session error, got {:?}", other),
+        }
+        assert!(matches!(client.submit(&batch), Err(SubmitError::Retry(_))));
+        assert!(matches!(client.submit(&batch), Err(SubmitError::Retry(_))));
+        assert!(matches!(client.submit(&batch), Err(SubmitError::Rejected(_))));
+    }
+}
@@ -0,0 +1,419 @@
+use crate::now_playing::{self, NowPlaying, PlaybackState};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tauri::{Listener, Manager};
+
+// Scrobbling services ignore tracks shorter than this.
+const MIN_TRACK_SECS: f64 = 30.0;
+// A track counts as listened after half its length or this long, whichever
+// comes first.
+const MAX_LISTEN_SECS: f64 = 240.0;
+// Time between two reports that counts as playback. Anything longer means
+// the reports stopped, e.g. because the machine was asleep.
+const MAX_REPORT_GAP_SECS: f64 = 30.0;
+// Oldest listens are dropped beyond this so an endless outage cannot grow
+// the queue file without bound.
+const MAX_QUEUE_LEN: usize = 5000;
+const RETRY_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Listen {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    // Seconds.
+    pub duration: Option<f64>,
+    // Unix time the track started playing.
+    pub started_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TrackEvent {
+    // A track started playing; services show it as "now playing".
+    Started(Listen),
+    // The track has been played long enough to count as a listen.
+    Listened(Listen),
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+// Follows the now-playing reports and works out when a track starts and
+// when it has been played long enough to submit, counting only the time
+// spent playing.
+#[derive(Debug, Default)]
+pub struct PlayTracker {
+    current: Option<Listen>,
+    played_secs: f64,
+    last_report: Option<Instant>,
+    last_position: Option<f64>,
+    playing: bool,
+    announced: bool,
+    listened: bool,
+}
+
+impl PlayTracker {
+    pub fn update(&mut self, np: &NowPlaying) -> Vec<TrackEvent> {
+        let now = Instant::now();
+        if self.playing
+            && let Some(last) = self.last_report {
+                let gap = now.duration_since(last).as_secs_f64();
+                if gap <= MAX_REPORT_GAP_SECS {
+                    self.played_secs += gap;
+                }
+            }
+        self.last_report = Some(now);
+        self.playing = np.state == PlaybackState::Playing;
+
+        let (Some(artist), Some(title)) = (&np.artist, &np.title) else {
+            self.current = None;
+            return Vec::new();
+        };
+        // The page may fill in the album or duration after the title, so only
+        // a duration that changes once known means a different track.
+        let same_track = self.current.as_ref().is_some_and(|c| {
+            c.artist == *artist
+                && c.title == *title
+                && !matches!((c.duration, np.duration), (Some(a), Some(b)) if (a - b).abs() >= 1.0)
+        });
+        // The same track starting over from the top counts as a new play.
+        let restarted = same_track
+            && matches!((self.last_position, np.position), (Some(last), Some(position)) if position < 5.0 && last > position + 10.0);
+        self.last_position = np.position;
+
+        if !same_track || restarted {
+            self.current = Some(Listen {
+                artist: artist.clone(),
+                title: title.clone(),
+                album: np.album.clone(),
+                duration: np.duration,
+                started_at: unix_now(),
+            });
+            self.played_secs = 0.0;
+            self.announced = false;
+            self.listened = false;
+        }
+
+        let mut events = Vec::new();
+        let Some(current) = self.current.as_mut() else {
+            return events;
+        };
+        if current.duration.is_none() {
+            current.duration = np.duration;
+        }
+        if current.album.is_none() {
+            current.album = np.album.clone();
+        }
+        if self.playing && !self.announced {
+            self.announced = true;
+            events.push(TrackEvent::Started(current.clone()));
+        }
+        if !self.listened && listened_enough(current.duration, self.played_secs) {
+            self.listened = true;
+            events.push(TrackEvent::Listened(current.clone()));
+        }
+        events
+    }
+}
+
+fn listened_enough(duration: Option<f64>, played_secs: f64) -> bool {
+    match duration {
+        Some(duration) if duration <= MIN_TRACK_SECS => false,
+        Some(duration) => played_secs >= (duration / 2.0).min(MAX_LISTEN_SECS),
+        None => played_secs >= MAX_LISTEN_SECS,
+    }
+}
+
+// Listens waiting to be submitted, kept in a JSON file in the config
+// directory so they survive restarts while the service is unreachable.
+pub struct ListenQueue {
+    path: Option<PathBuf>,
+    listens: Vec<Listen>,
+}
+
+impl ListenQueue {
+    pub fn load(file_name: &str) -> Self {
+        let path = match crate::paths::config_dir() {
+            Ok(dir) => Some(dir.join(file_name)),
+            Err(e) => {
+                eprintln!("Listens will not be kept across restarts: {}", e);
+                None
+            }
+        };
+        Self::open(path)
+    }
+
+    // Without a path the queue only lives in memory.
+    pub fn open(path: Option<PathBuf>) -> Self {
+        let listens = path.as_ref()
+            .filter(|path| path.exists())
+            .and_then(|path| {
+                std::fs::read_to_string(path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+                    .and_then(|contents| {
+                        serde_json::from_str(&contents)
+                            .map_err(|e| format!("Failed to parse {}: {}", path.display(), e))
+                    })
+                    .map_err(|e| eprintln!("{}", e))
+                    .ok()
+            })
+            .unwrap_or_default();
+        ListenQueue { path, listens }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listens.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.listens.len()
+    }
+
+    pub fn push(&mut self, listen: Listen) {
+        self.listens.push(listen);
+        if self.listens.len() > MAX_QUEUE_LEN {
+            let excess = self.listens.len() - MAX_QUEUE_LEN;
+            self.listens.drain(..excess);
+        }
+        self.save();
+    }
+
+    // The oldest listens, up to `max`.
+    pub fn peek(&self, max: usize) -> &[Listen] {
+        &self.listens[..max.min(self.listens.len())]
+    }
+
+    // Removes the first `count` listens once they have been submitted or
+    // rejected.
+    pub fn remove(&mut self, count: usize) {
+        self.listens.drain(..count.min(self.listens.len()));
+        self.save();
+    }
+
+    fn save(&self) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_vec_pretty(&self.listens)
+            .map_err(|e| format!("Failed to serialize listens: {}", e))
+            .and_then(|contents| crate::atomic_file::write(path, &contents));
+        if let Err(e) = result {
+            eprintln!("Failed to save listen queue: {}", e);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum SubmitError {
+    // Network trouble or a temporary server error; try again later.
+    Retry(String),
+    // The session key is no longer valid; log in again.
+    Session(String),
+    // The server refused the request; sending it again will not help.
+    Rejected(String),
+}
+
+impl std::fmt::Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::Retry(e) | SubmitError::Session(e) | SubmitError::Rejected(e) => f.write_str(e),
+        }
+    }
+}
+
+// A client for one listening service, logged in with one set of settings.
+pub trait ListenService: Send {
+    fn now_playing(&mut self, listen: &Listen) -> Result<(), SubmitError>;
+    fn submit(&mut self, listens: &[Listen]) -> Result<(), SubmitError>;
+}
+
+// Submits queued listens in batches, oldest first, until the queue is empty
+// or the server cannot take them right now. Rejected batches are dropped.
+pub fn flush_queue(
+    queue: &mut ListenQueue,
+    client: &mut dyn ListenService,
+    batch_size: usize,
+    name: &str,
+) -> Result<(), SubmitError> {
+    while !queue.is_empty() {
+        let batch = queue.peek(batch_size).to_vec();
+        match client.submit(&batch) {
+            Ok(()) => queue.remove(batch.len()),
+            Err(SubmitError::Rejected(e)) => {
+                eprintln!("{} rejected {} listens, dropping them: {}", name, batch.len(), e);
+                queue.remove(batch.len());
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(())
+}
+
+pub struct Service<S> {
+    // For log messages.
+    pub name: &'static str,
+    pub queue_file: &'static str,
+    // Most listens sent in one request.
+    pub batch_size: usize,
+    // This service's settings, or None while it is turned off.
+    pub settings: fn(&crate::settings::Settings) -> Option<S>,
+    pub connect: fn(S) -> Box<dyn ListenService>,
+}
+
+enum Message {
+    NowPlaying(NowPlaying),
+    SettingsChanged,
+}
+
+struct Submitter<S> {
+    app: tauri::AppHandle,
+    service: Service<S>,
+    tracker: PlayTracker,
+    queue: ListenQueue,
+    client: Option<(S, Box<dyn ListenService>)>,
+    // Set after a failed submission; the queue is left alone until then.
+    retry_at: Option<Instant>,
+}
+
+impl<S: Clone + PartialEq> Submitter<S> {
+    // The client for the current settings, or None while the service is off.
+    // A new client, and so a new login, is made whenever the settings change.
+    fn client(&mut self) -> Option<&mut Box<dyn ListenService>> {
+        let state = self.app.state::<crate::AppState>();
+        let settings = state.settings.lock().ok().and_then(|s| (self.service.settings)(&s));
+        let Some(settings) = settings else {
+            self.client = None;
+            return None;
+        };
+        if self.client.as_ref().is_none_or(|(current, _)| *current != settings) {
+            self.client = Some((settings.clone(), (self.service.connect)(settings)));
+            self.retry_at = None;
+        }
+        self.client.as_mut().map(|(_, client)| client)
+    }
+
+    fn on_now_playing(&mut self, np: &NowPlaying) {
+        let events = self.tracker.update(np);
+        for event in events {
+            let Some(client) = self.client() else {
+                return;
+            };
+            match event {
+                TrackEvent::Started(listen) => {
+                    if let Err(e) = client.now_playing(&listen) {
+                        eprintln!("Failed to send now playing to {}: {}", self.service.name, e);
+                    }
+                }
+                TrackEvent::Listened(listen) => {
+                    self.queue.push(listen);
+                    self.flush();
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.retry_at.is_some_and(|at| Instant::now() < at) {
+            return;
+        }
+        self.retry_at = None;
+        if self.client().is_none() {
+            return;
+        }
+        let Some((_, client)) = self.client.as_mut() else {
+            return;
+        };
+        let result = flush_queue(&mut self.queue, client.as_mut(), self.service.batch_size, self.service.name);
+        if let Err(e) = result {
+            eprintln!(
+                "Failed to submit to {}, {} listens queued for later: {}",
+                self.service.name, self.queue.len(), e
+            );
+            self.retry_at = Some(Instant::now() + RETRY_INTERVAL);
+        }
+    }
+
+    fn run(mut self, messages: mpsc::Receiver<Message>) {
+        loop {
+            match messages.recv_timeout(RETRY_INTERVAL) {
+                Ok(Message::NowPlaying(np)) => self.on_now_playing(&np),
+                // New credentials get a fresh attempt at the backlog.
+                Ok(Message::SettingsChanged) => self.retry_at = None,
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+            if !self.queue.is_empty() {
+                self.flush();
+            }
+        }
+    }
+}
+
+// Submits listens from the now-playing reports on a thread of its own, so
+// slow or unreachable servers never hold up the UI.
+pub fn start<S: Clone + PartialEq + Send + 'static>(app: tauri::AppHandle, service: Service<S>) {
+    let (sender, messages) = mpsc::channel();
+
+    let now_playing_sender = sender.clone();
+    app.listen(now_playing::CHANGED_EVENT, move |event| {
+        if let Ok(np) = serde_json::from_str::<NowPlaying>(event.payload()) {
+            let _ = now_playing_sender.send(Message::NowPlaying(np));
+        }
+    });
+    app.listen("settings-changed", move |_| {
+        let _ = sender.send(Message::SettingsChanged);
+    });
+
+    let submitter = Submitter {
+        app,
+        queue: ListenQueue::load(service.queue_file),
+        service,
+        tracker: PlayTracker::default(),
+        client: None,
+        retry_at: None,
+    };
+    std::thread::spawn(move || submitter.run(messages));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing(title: &str, album: Option<&str>, duration: Option<f64>) -> NowPlaying {
+        NowPlaying {
+            title: Some(title.to_string()),
+            artist: Some("Artist".to_string()),
+            album: album.map(str::to_string),
+            duration,
+            position: Some(1.0),
+            state: PlaybackState::Playing,
+            ..NowPlaying::default()
+        }
+    }
+
+    #[test]
+    fn album_filled_in_later_is_the_same_track() {
+        let mut tracker = PlayTracker::default();
+        let events = tracker.update(&playing("One", None, None));
+        assert!(matches!(&events[..], [TrackEvent::Started(listen)] if listen.album.is_none()));
+
+        assert!(tracker.update(&playing("One", Some("Album"), Some(200.0))).is_empty());
+        let current = tracker.current.as_ref().unwrap();
+        assert_eq!(current.album.as_deref(), Some("Album"));
+        assert_eq!(current.duration, Some(200.0));
+    }
+
+    #[test]
+    fn a_different_title_or_duration_is_a_new_track() {
+        let mut tracker = PlayTracker::default();
+        tracker.update(&playing("One", Some("Album"), Some(200.0)));
+
+        let events = tracker.update(&playing("One", Some("Album"), Some(320.0)));
+        assert!(matches!(&events[..], [TrackEvent::Started(listen)] if listen.duration == Some(320.0)));
+        let events = tracker.update(&playing("Two", Some("Album"), Some(320.0)));
+        assert!(matches!(&events[..], [TrackEvent::Started(listen)] if listen.title == "Two"));
+    }
+}
@@ -1,19 +1,59 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use tauri::{
-    Manager, WindowEvent,
-    menu::{Menu, MenuItem},
-    tray::{MouseButton, TrayIconBuilder, TrayIconEvent},
-};
+use tauri::{Emitter, Manager, WindowEvent};
 use raw_window_handle::HasWindowHandle;
+use std::path::Path;
 use std::sync::Mutex;
+use tauri_plugin_dialog::DialogExt;
 
+mod atomic_file;
+mod autostart;
+mod bundle;
+mod cli;
+mod config;
+mod control;
+mod covers;
+mod discord;
+mod file_output;
+mod keymap;
+mod listenbrainz;
+mod listens;
+mod mpris;
+mod now_playing;
+mod paths;
+mod player;
+mod policy;
+mod remote_api;
+mod scrobble;
+mod selectors;
 mod thumbar;
 mod settings;
+mod settings_watcher;
+mod shortcuts;
+mod template;
+#[cfg(test)]
+mod test_support;
+mod tray;
+mod tray_icon;
 mod window_manager;
 
 pub struct AppState {
     settings: Mutex<settings::Settings>,
+    autostart_issue: Mutex<Option<String>>,
+    settings_warning: Mutex<Option<String>>,
+    config: Mutex<config::LayeredConfig>,
+    shortcut_issue: Mutex<Option<String>>,
+    now_playing: Mutex<now_playing::NowPlaying>,
+    // The bundle chosen in the import dialog, waiting for confirmation.
+    pending_import: Mutex<Option<std::path::PathBuf>>,
+}
+
+#[derive(serde::Serialize)]
+struct SettingsResponse {
+    settings: settings::Settings,
+    locked_fields: Vec<String>,
+    sources: std::collections::BTreeMap<String, config::Source>,
+    tray_clicks: bool,
 }
 
 #[tauri::command]
@@ -27,10 +67,88 @@ fn native_remove_thumb_buttons() {
 }
 
 #[tauri::command]
-fn get_settings(state: tauri::State<AppState>) -> Result<settings::Settings, String> {
+fn player_command(app: tauri::AppHandle, command: player::PlayerCommand) -> Result<(), String> {
+    player::dispatch(&app, &command)
+}
+
+#[tauri::command]
+fn get_now_playing(state: tauri::State<AppState>) -> Result<now_playing::NowPlaying, String> {
+    let now_playing = state.now_playing.lock()
+        .map_err(|e| format!("Failed to lock now playing: {}", e))?;
+    Ok(now_playing.clone())
+}
+
+#[tauri::command]
+fn report_now_playing(app: tauri::AppHandle, now_playing: now_playing::NowPlaying) -> Result<(), String> {
+    now_playing::update(&app, now_playing)
+}
+
+// The probe waits for the page to answer, so it runs on a blocking thread
+// rather than a runtime worker or the main thread the page needs.
+#[tauri::command]
+async fn check_selectors(app: tauri::AppHandle) -> Result<selectors::SelectorReport, String> {
+    tauri::async_runtime::spawn_blocking(move || selectors::check(&app))
+        .await
+        .map_err(|e| format!("Failed to check selectors: {}", e))?
+}
+
+#[tauri::command]
+fn report_selector_matches(probe: u64, results: std::collections::BTreeMap<String, Vec<selectors::SelectorMatch>>) {
+    selectors::deliver_probe(probe, results);
+}
+
+#[tauri::command]
+fn get_settings(state: tauri::State<AppState>) -> Result<SettingsResponse, String> {
+    let settings = state.settings.lock()
+        .map_err(|e| format!("Failed to lock settings: {}", e))?;
+    let config = state.config.lock()
+        .map_err(|e| format!("Failed to lock settings: {}", e))?;
+    Ok(SettingsResponse {
+        settings: settings.redacted(),
+        locked_fields: config.policy().locked_fields(),
+        sources: config.resolve()?.sources,
+        tray_clicks: tray::REPORTS_CLICKS,
+    })
+}
+
+#[tauri::command]
+fn get_settings_warning(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let warning = state.settings_warning.lock()
+        .map_err(|e| format!("Failed to lock settings: {}", e))?;
+    Ok(warning.clone())
+}
+
+#[tauri::command]
+fn get_app_paths() -> Result<paths::AppPaths, String> {
+    paths::get().cloned()
+}
+
+#[tauri::command]
+fn get_autostart_issue(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let issue = state.autostart_issue.lock()
+        .map_err(|e| format!("Failed to lock autostart state: {}", e))?;
+    Ok(issue.clone())
+}
+
+#[tauri::command]
+fn get_keymap(state: tauri::State<AppState>) -> Result<Vec<keymap::KeymapEntry>, String> {
     let settings = state.settings.lock()
         .map_err(|e| format!("Failed to lock settings: {}", e))?;
-    Ok(settings.clone())
+    Ok(settings.keymap.entries())
+}
+
+#[tauri::command]
+fn run_app_action(app: tauri::AppHandle, action: String) -> Result<(), String> {
+    let action = keymap::AppAction::from_id(&action)
+        .ok_or_else(|| format!("Unknown action \"{}\"", action))?;
+    keymap::run(&app, action)
+}
+
+#[tauri::command]
+fn get_shortcut_issue(state: tauri::State<AppState>) -> Result<Option<String>, String> {
+    let issue = state.shortcut_issue.lock()
+        .map_err(|e| format!("Failed to lock shortcut state: {}", e))?;
+    Ok(issue.clone())
 }
 
 #[tauri::command]
@@ -52,21 +170,178 @@ fn minimize_window(app: tauri::AppHandle, state: tauri::State<AppState>) -> Resu
     Ok(())
 }
 
+// The overlay sends secrets back as REDACTED unless they were edited, and the
+// scrobbling password only when the user typed one to log in.
 #[tauri::command]
-fn save_settings(settings: settings::Settings, state: tauri::State<AppState>) -> Result<(), String> {
-    settings.save()?;
+async fn save_settings(
+    app: tauri::AppHandle,
+    mut settings: settings::Settings,
+    scrobbling_password: Option<String>,
+) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    {
+        let current = state.settings.lock()
+            .map_err(|e| format!("Failed to lock settings: {}", e))?;
+        settings.restore_secrets(&current);
+    }
+    if let Some(password) = scrobbling_password.filter(|p| !p.is_empty()) {
+        let scrobbling = settings.scrobbling.clone();
+        // Off the main thread; the server may take a while to answer.
+        settings.scrobbling.session_key = tauri::async_runtime::spawn_blocking(move || {
+            scrobble::log_in(&scrobbling, &password)
+        })
+        .await
+        .map_err(|e| format!("Failed to log in: {}", e))??;
+    }
+    settings.validate()?;
+    
+    let resolved = state.config.lock()
+        .map_err(|e| format!("Failed to lock settings: {}", e))?
+        .save(&settings)?;
+    
+    if let Ok(mut warning) = state.settings_warning.lock() {
+        *warning = None;
+    }
+    
+    apply_settings(&app, resolved.settings)
+}
+
+// Swaps new settings into AppState and re-applies everything that depends on
+// them. Shared by save_settings and the settings.json watcher.
+pub fn apply_settings(app: &tauri::AppHandle, settings: settings::Settings) -> Result<(), String> {
+    let state = app.state::<AppState>();
     
     let mut app_settings = state.settings.lock()
         .map_err(|e| format!("Failed to lock settings: {}", e))?;
     
-    if settings.launch_on_login {
-        settings::autostart::enable(&settings.launch_mode)?;
-    } else {
-        let _ = settings::autostart::disable();
+    *app_settings = settings.clone();
+    drop(app_settings);
+    
+    let result = autostart::reconcile(settings.launch_on_login, &settings.launch_mode);
+    if let Ok(mut issue) = state.autostart_issue.lock() {
+        *issue = result.clone().err();
     }
+    apply_shortcuts(app, &settings.keybindings);
     
-    *app_settings = settings.clone();
+    let _ = app.emit("settings-changed", &settings.redacted());
     
+    result
+}
+
+// The keybindings last registered successfully. On Wayland registering them
+// means a new GlobalShortcuts portal session, which is slow and may prompt the
+// user again, so saves that leave them unchanged skip it.
+static APPLIED_KEYBINDINGS: Mutex<Option<settings::Keybindings>> = Mutex::new(None);
+
+fn apply_shortcuts(app: &tauri::AppHandle, keybindings: &settings::Keybindings) {
+    if APPLIED_KEYBINDINGS.lock().is_ok_and(|applied| applied.as_ref() == Some(keybindings)) {
+        return;
+    }
+    let result = shortcuts::apply(app, keybindings);
+    if let Ok(mut applied) = APPLIED_KEYBINDINGS.lock() {
+        *applied = result.is_ok().then(|| keybindings.clone());
+    }
+    if let Err(e) = &result {
+        eprintln!("Failed to apply shortcuts: {}", e);
+    }
+    let state = app.state::<AppState>();
+    if let Ok(mut issue) = state.shortcut_issue.lock() {
+        *issue = result.err();
+    }
+}
+
+#[tauri::command]
+async fn export_config(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    let Some(path) = app.dialog().file()
+        .add_filter("Qobuz Player configuration", &["json"])
+        .set_file_name("qobuz-player-config.json")
+        .blocking_save_file() else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| format!("Invalid export path: {}", e))?;
+    
+    let file_settings = {
+        let state = app.state::<AppState>();
+        let config = state.config.lock()
+            .map_err(|e| format!("Failed to lock settings: {}", e))?;
+        config.file().clone()
+    };
+    bundle::ConfigBundle::export(&file_settings, &paths::config_dir()?)?.write(&path)?;
+    
+    Ok(Some(path.display().to_string()))
+}
+
+#[tauri::command]
+async fn preview_config_import(app: tauri::AppHandle) -> Result<Option<bundle::ImportPreview>, String> {
+    let Some(path) = app.dialog().file()
+        .add_filter("Qobuz Player configuration", &["json"])
+        .blocking_pick_file() else {
+        return Ok(None);
+    };
+    let path = path.into_path().map_err(|e| format!("Invalid import path: {}", e))?;
+    
+    let bundle = bundle::ConfigBundle::read(&path)?;
+    let state = app.state::<AppState>();
+    let preview = {
+        let config = state.config.lock()
+            .map_err(|e| format!("Failed to lock settings: {}", e))?;
+        bundle.preview(&path, config.file())?
+    };
+    if let Ok(mut pending) = state.pending_import.lock() {
+        *pending = Some(path);
+    }
+    Ok(Some(preview))
+}
+
+// Imports the file last picked in preview_config_import; the page never
+// gets to name a path itself.
+#[tauri::command]
+fn import_config(app: tauri::AppHandle, state: tauri::State<AppState>) -> Result<(), String> {
+    let path = state.pending_import.lock()
+        .map_err(|e| format!("Failed to lock import state: {}", e))?
+        .clone()
+        .ok_or_else(|| "No configuration file has been chosen to import".to_string())?;
+    let bundle = bundle::ConfigBundle::read(&path)?;
+    let resolved = {
+        let mut config = state.config.lock()
+            .map_err(|e| format!("Failed to lock settings: {}", e))?;
+        bundle::import(&bundle, &mut config, &paths::config_dir()?)?
+    };
+    if let Ok(mut pending) = state.pending_import.lock() {
+        *pending = None;
+    }
+    
+    if let Ok(mut warning) = state.settings_warning.lock() {
+        *warning = None;
+    }
+    
+    apply_settings(&app, resolved.settings)
+}
+
+fn cli_export_config(config: &config::LayeredConfig, path: &Path) -> Result<(), String> {
+    bundle::ConfigBundle::export(config.file(), &paths::config_dir()?)?.write(path)?;
+    println!("Exported configuration to {}", path.display());
+    Ok(())
+}
+
+fn cli_import_config(config: &mut config::LayeredConfig, path: &Path) -> Result<(), String> {
+    let bundle = bundle::ConfigBundle::read(path)?;
+    let preview = bundle.preview(path, config.file())?;
+    
+    if preview.changes.is_empty() {
+        println!("No settings changes.");
+    }
+    for change in &preview.changes {
+        let show = |v: &Option<serde_json::Value>| v.as_ref().map(|v| v.to_string()).unwrap_or_else(|| "(unset)".to_string());
+        println!("  {}: {} -> {}", change.key, show(&change.current), show(&change.incoming));
+    }
+    for file in &preview.files {
+        println!("  {} will be replaced", file);
+    }
+    
+    let resolved = bundle::import(&bundle, config, &paths::config_dir()?)?;
+    autostart::reconcile(resolved.settings.launch_on_login, &resolved.settings.launch_mode)?;
+    println!("Imported configuration from {}", path.display());
     Ok(())
 }
 
@@ -242,16 +517,107 @@ fn open_settings_window(app: tauri::AppHandle) -> Result<(), String> {
 }
 
 fn main() {
-    let app_settings = settings::Settings::load();
+    let cli_args = match cli::parse(std::env::args().skip(1)) {
+        Ok(cli::CliAction::Run(args)) => args,
+        Ok(cli::CliAction::Help) => {
+            println!("{}", cli::usage());
+            return;
+        }
+        Ok(cli::CliAction::Version) => {
+            println!("{}", cli::version());
+            return;
+        }
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::usage());
+            std::process::exit(2);
+        }
+    };
+    let launch_mode = cli_args.launch_mode.unwrap_or(settings::LaunchMode::Restored);
+    let startup_command = cli_args.command.clone();
+    
+    if let Err(e) = paths::init(cli_args.config_dir.clone()) {
+        eprintln!("Failed to resolve app directories: {}", e);
+    }
+
+    let load_result = settings::Settings::load();
+    let settings_warning = load_result.warning();
+    if let Some(warning) = &settings_warning {
+        eprintln!("{}", warning);
+    }
+    
+    let policy = policy::Policy::load().unwrap_or_else(|e| {
+        eprintln!("Ignoring administrator policy: {}", e);
+        policy::Policy::default()
+    });
+    let environment = config::environment_overrides().unwrap_or_else(|e| {
+        eprintln!("Ignoring QOBUZ_PLAYER_* environment overrides: {}", e);
+        Default::default()
+    });
+    let command_line = match config::command_line_overrides(&cli_args.set) {
+        Ok(overrides) => overrides,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, cli::usage());
+            std::process::exit(2);
+        }
+    };
+    let mut layered_config = config::LayeredConfig::new(
+        load_result.settings.clone(),
+        load_result.file_keys.clone(),
+        environment,
+        command_line,
+        policy,
+    );
+    let cli_command = match (&cli_args.export_config, &cli_args.import_config) {
+        (Some(path), _) => Some(cli_export_config(&layered_config, path)),
+        (None, Some(path)) => Some(cli_import_config(&mut layered_config, path)),
+        (None, None) => None,
+    };
+    if let Some(result) = cli_command {
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    
+    let app_settings = match layered_config.resolve() {
+        Ok(resolved) => resolved.settings,
+        Err(e) => {
+            eprintln!("Failed to resolve settings: {}", e);
+            load_result.settings.clone()
+        }
+    };
+    
+    selectors::init();
     
     tauri::Builder::default()
         .manage(AppState {
             settings: Mutex::new(app_settings),
+            autostart_issue: Mutex::new(None),
+            settings_warning: Mutex::new(settings_warning),
+            config: Mutex::new(layered_config),
+            shortcut_issue: Mutex::new(None),
+            now_playing: Mutex::new(now_playing::NowPlaying::default()),
+            pending_import: Mutex::new(None),
         })
         .invoke_handler(tauri::generate_handler![
             native_add_thumb_buttons, 
             native_remove_thumb_buttons,
+            player_command,
+            check_selectors,
+            report_selector_matches,
+            get_now_playing,
+            report_now_playing,
             get_settings,
+            get_settings_warning,
+            get_autostart_issue,
+            get_shortcut_issue,
+            get_keymap,
+            run_app_action,
+            get_app_paths,
+            export_config,
+            preview_config_import,
+            import_config,
             save_settings,
             close_settings_window,
             open_settings_window,
@@ -259,14 +625,48 @@ fn main() {
             apply_theme_from_string
         ])
     .plugin(tauri_plugin_media::init())
-    .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
-        if let Some(window) = app.get_webview_window("main") {
-            let _ = window.unminimize();
-            let _ = window.show();
-            let _ = window.set_focus();
+    .plugin(tauri_plugin_dialog::init())
+    .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+    .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
+        let command = match cli::parse(args.into_iter().skip(1)) {
+            Ok(cli::CliAction::Run(args)) => args.command,
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Ignoring forwarded arguments: {}", e);
+                None
+            }
+        };
+        
+        let command = command.unwrap_or(control::ControlCommand::Show);
+        if let Err(e) = control::run(app, &command) {
+            eprintln!("Failed to run forwarded command: {}", e);
         }
     }))
-        .setup(|app| {
+        .setup(move |app| {
+            // Getting this far means no other instance was running to take
+            // the command.
+            if let Some(command) = &startup_command
+                && command.needs_running_instance() {
+                    eprintln!("No running instance of Qobuz Player to send the command to");
+                    std::process::exit(1);
+                }
+
+            // Only here, in the primary instance: invocations that just
+            // forward a command to it must not touch the autostart entry.
+            {
+                let state = app.state::<AppState>();
+                let settings = state.settings.lock().map(|s| s.clone()).unwrap_or_default();
+                let issue = autostart::reconcile(settings.launch_on_login, &settings.launch_mode).err();
+                if let Some(issue) = &issue {
+                    eprintln!("Autostart reconciliation failed: {}", issue);
+                }
+                if let Ok(mut autostart_issue) = state.autostart_issue.lock() {
+                    *autostart_issue = issue;
+                }
+            }
+
+            window_manager::create_main_window(app)?;
+            
             #[cfg(target_os = "windows")]
             {
                 use windows::core::PCWSTR;
@@ -282,73 +682,20 @@ fn main() {
                 let _ = unsafe { SetCurrentProcessExplicitAppUserModelID(pcw) };
             }
             
-            let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
-            let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show, &settings, &quit])?;
-
-            TrayIconBuilder::new()
-                .icon(app.default_window_icon().unwrap().clone())
-                .menu(&menu)
-                .show_menu_on_left_click(true)
-                .on_menu_event(|app, event| match event.id().as_ref() {
-                    "quit" => {
-                        thumbar::cleanup_thumbar();
-                        window_manager::remove_minimize_hook();
-                        std::process::exit(0);
-                    }
-                    "show" => {
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.unminimize();
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                            
-                            #[cfg(target_os = "windows")]
-                            if let Ok(wh) = window.window_handle()
-                                && let raw_window_handle::RawWindowHandle::Win32(h) = wh.into() {
-                                    thumbar::set_stored_hwnd(h);
-                                    thumbar::add_thumb_buttons();
-                                }
-                        }
-                    }
-                    "settings" => {
-                        let _ = open_settings_window(app.clone());
-                    }
-                    _ => {}
-                })
-                .on_tray_icon_event(|tray, event| {
-                    if let TrayIconEvent::DoubleClick {
-                        button: MouseButton::Left,
-                        ..
-                    } = event {
-                        let app = tray.app_handle();
-                        if let Some(window) = app.get_webview_window("main") {
-                            let _ = window.eval(r#"
-                                (function() {
-                                    const overlay = document.getElementById('qobuz-settings-overlay');
-                                    if (overlay) document.body.removeChild(overlay);
-                                    const backBtn = document.getElementById('qobuz-settings-back-btn');
-                                    if (backBtn) document.body.removeChild(backBtn);
-                                })();
-                            "#);
-                            
-                            let _ = window.unminimize();
-                            let _ = window.show();
-                            let _ = window.set_focus();
-                            
-                            #[cfg(target_os = "windows")]
-                            if let Ok(wh) = window.window_handle()
-                                && let raw_window_handle::RawWindowHandle::Win32(h) = wh.into() {
-                                    thumbar::set_stored_hwnd(h);
-                                    thumbar::add_thumb_buttons();
-                                }
-                        }
-                    }
-                })
-                .build(app)?;
+            tray::init(app)?;
             
             thumbar::init_thumbar(app, "main");
             window_manager::init_window_manager(app);
+            settings_watcher::start(app.handle().clone());
+            mpris::start(app.handle().clone());
+            scrobble::start(app.handle().clone());
+            listenbrainz::start(app.handle().clone());
+            discord::start(app.handle().clone());
+            file_output::start(app.handle().clone());
+            remote_api::start(app.handle().clone());
+            if let Ok(settings) = app.state::<AppState>().settings.lock().map(|s| s.clone()) {
+                apply_shortcuts(app.handle(), &settings.keybindings);
+            }
             
             if let Some(window) = app.get_webview_window("main") {
                 let init_script = r#"
@@ -433,7 +780,7 @@ fn main() {
                         titlebar.id = 'custom-titlebar';
                         titlebar.innerHTML = `
                             <div id="custom-titlebar-controls">
-                                <button class="titlebar-button settings-btn" id="titlebar-settings" title="Settings">
+                                <button class="titlebar-button settings-btn" id="titlebar-settings" title="Settings" data-keymap-action="open_settings">
                                     <svg width="16" height="16" viewBox="0 0 512 512">
                                         <path d="M496,293.984c9.031-0.703,16-8.25,16-17.297v-41.375c0-9.063-6.969-16.594-16-17.313l-54.828-4.281 c-3.484-0.266-6.484-2.453-7.828-5.688l-18.031-43.516c-1.344-3.219-0.781-6.906,1.5-9.547l35.75-41.813 c5.875-6.891,5.5-17.141-0.922-23.547l-29.25-29.25c-6.406-6.406-16.672-6.813-23.547-0.922l-41.813,35.75 c-2.641,2.266-6.344,2.844-9.547,1.516l-43.531-18.047c-3.219-1.328-5.422-4.375-5.703-7.828l-4.266-54.813 C293.281,6.969,285.75,0,276.688,0h-41.375c-9.063,0-16.594,6.969-17.297,16.016l-4.281,54.813c-0.266,3.469-2.469,6.5-5.688,7.828 l-43.531,18.047c-3.219,1.328-6.906,0.75-9.563-1.516l-41.797-35.75c-6.875-5.891-17.125-5.484-23.547,0.922l-29.25,29.25 c-6.406,6.406-6.797,16.656-0.922,23.547l35.75,41.813c2.25,2.641,2.844,6.328,1.5,9.547l-18.031,43.516 c-1.313,3.234-4.359,5.422-7.813,5.688L16,218c-9.031,0.719-16,8.25-16,17.313v41.359c0,9.063,6.969,16.609,16,17.313l54.844,4.266 c3.453,0.281,6.5,2.484,7.813,5.703l18.031,43.516c1.344,3.219,0.75,6.922-1.5,9.563l-35.75,41.813 c-5.875,6.875-5.484,17.125,0.922,23.547l29.25,29.25c6.422,6.406,16.672,6.797,23.547,0.906l41.797-35.75 c2.656-2.25,6.344-2.844,9.563-1.5l43.531,18.031c3.219,1.344,5.422,4.359,5.688,7.844l4.281,54.813 c0.703,9.031,8.234,16.016,17.297,16.016h41.375c9.063,0,16.594-6.984,17.297-16.016l4.266-54.813 c0.281-3.484,2.484-6.5,5.703-7.844l43.531-18.031c3.203-1.344,6.922-0.75,9.547,1.5l41.813,35.75 c6.875,5.891,17.141,5.5,23.547-0.906l29.25-29.25c6.422-6.422,6.797-16.672,0.922-23.547l-35.75-41.813 c-2.25-2.641-2.844-6.344-1.5-9.563l18.031-43.516c1.344-3.219,4.344-5.422,7.828-5.703L496,293.984z M256,342.516 c-23.109,0-44.844-9-61.188-25.328c-16.344-16.359-25.344-38.078-25.344-61.203c0-23.109,9-44.844,25.344-61.172 c16.344-16.359,38.078-25.344,61.188-25.344c23.125,0,44.844,8.984,61.188,25.344c16.344,16.328,25.344,38.063,25.344,61.172 c0,23.125-9,44.844-25.344,61.203C300.844,333.516,279.125,342.516,256,342.516z"/>
                                     </svg>
@@ -475,6 +822,7 @@ fn main() {
                             };
                             document.getElementById('titlebar-close').onclick = () => currentWindow.close();
                             document.getElementById('titlebar-settings').onclick = () => invoke('open_settings_window');
+                            if (window.__qobuzRefreshKeymap) window.__qobuzRefreshKeymap();
                             
                             function detectAndApplyTheme() {
                                 const html = document.documentElement;
@@ -528,8 +876,15 @@ fn main() {
                         window_manager::set_main_window_hwnd(h.hwnd.get());
                         window_manager::install_minimize_hook();
                     }
+
+                window_manager::apply_launch_mode(&window, &launch_mode);
             }
             
+            if let Some(command) = &startup_command
+                && let Err(e) = control::run(app.handle(), command) {
+                    eprintln!("Failed to run {:?}: {}", command, e);
+                }
+            
             Ok(())
         })
         .on_window_event(|window, event| {
@@ -0,0 +1,545 @@
+#[cfg(not(target_os = "linux"))]
+mod stub {
+    pub fn start(_app: tauri::AppHandle) {}
+}
+
+#[cfg(not(target_os = "linux"))]
+pub use stub::*;
+
+#[cfg(target_os = "linux")]
+mod linux_impl {
+    use crate::control::ControlCommand;
+    use crate::now_playing::{self, NowPlaying, PlaybackState};
+    use crate::player::PlayerCommand;
+    use std::collections::HashMap;
+    use std::hash::{DefaultHasher, Hash, Hasher};
+    use std::sync::{Arc, Mutex, OnceLock};
+    use std::time::Instant;
+    use tauri::{Listener, Manager};
+    use zbus::blocking::connection;
+    use zbus::object_server::SignalEmitter;
+    use zbus::zvariant::{ObjectPath, OwnedValue, Value};
+    use zbus::{fdo, interface};
+
+    pub const BUS_NAME: &str = "org.mpris.MediaPlayer2.qobuz_player";
+    const OBJECT_PATH: &str = "/org/mpris/MediaPlayer2";
+    const NO_TRACK: &str = "/org/mpris/MediaPlayer2/TrackList/NoTrack";
+    // A reported position further than this from where playback should be
+    // counts as a seek and is announced with the Seeked signal.
+    const SEEK_THRESHOLD_SECS: f64 = 2.0;
+
+    static CONNECTION: OnceLock<zbus::blocking::Connection> = OnceLock::new();
+
+    // What the D-Bus interfaces need from the app. Kept narrow so the tests
+    // below can run the server on a private bus without a webview.
+    pub trait Backend: Send + Sync + 'static {
+        fn run(&self, command: ControlCommand) -> Result<(), String>;
+        fn now_playing(&self) -> NowPlaying;
+    }
+
+    struct AppBackend(tauri::AppHandle);
+
+    impl Backend for AppBackend {
+        fn run(&self, command: ControlCommand) -> Result<(), String> {
+            crate::control::run(&self.0, &command)
+        }
+
+        fn now_playing(&self) -> NowPlaying {
+            let state = self.0.state::<crate::AppState>();
+            state.now_playing.lock().map(|np| np.clone()).unwrap_or_default()
+        }
+    }
+
+    fn micros(seconds: f64) -> i64 {
+        (seconds * 1_000_000.0) as i64
+    }
+
+    fn track_id(np: &NowPlaying) -> String {
+        if np.title.is_none() {
+            return NO_TRACK.to_string();
+        }
+        let mut hasher = DefaultHasher::new();
+        (&np.title, &np.artist, &np.album).hash(&mut hasher);
+        format!("/org/qobuz_player/track/{:x}", hasher.finish())
+    }
+
+    fn metadata(np: &NowPlaying) -> HashMap<String, OwnedValue> {
+        let mut map = HashMap::new();
+        let mut insert = |key: &str, value: Value<'_>| {
+            if let Ok(value) = value.try_to_owned() {
+                map.insert(key.to_string(), value);
+            }
+        };
+
+        if let Ok(path) = ObjectPath::try_from(track_id(np)) {
+            insert("mpris:trackid", Value::from(path));
+        }
+        if let Some(duration) = np.duration {
+            insert("mpris:length", Value::from(micros(duration)));
+        }
+        if let Some(cover_url) = &np.cover_url {
+            insert("mpris:artUrl", Value::from(cover_url.as_str()));
+        }
+        if let Some(title) = &np.title {
+            insert("xesam:title", Value::from(title.as_str()));
+        }
+        if let Some(artist) = &np.artist {
+            insert("xesam:artist", Value::from(vec![artist.as_str()]));
+        }
+        if let Some(album) = &np.album {
+            insert("xesam:album", Value::from(album.as_str()));
+        }
+        map
+    }
+
+    fn failed(e: String) -> fdo::Error {
+        fdo::Error::Failed(e)
+    }
+
+    struct Root {
+        backend: Arc<dyn Backend>,
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2")]
+    impl Root {
+        fn raise(&self) -> fdo::Result<()> {
+            self.backend.run(ControlCommand::Show).map_err(failed)
+        }
+
+        fn quit(&self) -> fdo::Result<()> {
+            self.backend.run(ControlCommand::Quit).map_err(failed)
+        }
+
+        #[zbus(property)]
+        fn can_quit(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_raise(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn has_track_list(&self) -> bool {
+            false
+        }
+
+        #[zbus(property)]
+        fn identity(&self) -> String {
+            "Qobuz Player".to_string()
+        }
+
+        #[zbus(property)]
+        fn desktop_entry(&self) -> String {
+            "qobuz-player".to_string()
+        }
+
+        #[zbus(property)]
+        fn supported_uri_schemes(&self) -> Vec<String> {
+            vec!["https".to_string()]
+        }
+
+        #[zbus(property)]
+        fn supported_mime_types(&self) -> Vec<String> {
+            Vec::new()
+        }
+    }
+
+    struct Player {
+        backend: Arc<dyn Backend>,
+    }
+
+    impl Player {
+        fn player(&self, command: PlayerCommand) -> fdo::Result<()> {
+            self.backend.run(ControlCommand::Player(command)).map_err(failed)
+        }
+    }
+
+    #[interface(name = "org.mpris.MediaPlayer2.Player")]
+    impl Player {
+        fn play(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Play)
+        }
+
+        fn pause(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Pause)
+        }
+
+        fn play_pause(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Toggle)
+        }
+
+        // The web player has no stop; pausing is the closest equivalent.
+        fn stop(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Pause)
+        }
+
+        fn next(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Next)
+        }
+
+        fn previous(&self) -> fdo::Result<()> {
+            self.player(PlayerCommand::Previous)
+        }
+
+        fn seek(&self, offset: i64) -> fdo::Result<()> {
+            let np = self.backend.now_playing();
+            let Some(current) = now_playing::position_now(&np) else {
+                return Ok(());
+            };
+            let position = (current + offset as f64 / 1_000_000.0).max(0.0);
+            // Seeking past the end skips to the next track, as the spec asks.
+            if np.duration.is_some_and(|duration| position >= duration) {
+                return self.player(PlayerCommand::Next);
+            }
+            self.player(PlayerCommand::Seek { position })
+        }
+
+        fn set_position(&self, track_id: ObjectPath<'_>, position: i64) -> fdo::Result<()> {
+            let np = self.backend.now_playing();
+            // Stale requests for a track that is no longer playing are ignored.
+            if track_id.as_str() != self::track_id(&np) || position < 0 {
+                return Ok(());
+            }
+            let position = position as f64 / 1_000_000.0;
+            if np.duration.is_some_and(|duration| position > duration) {
+                return Ok(());
+            }
+            self.player(PlayerCommand::Seek { position })
+        }
+
+        fn open_uri(&self, uri: String) -> fdo::Result<()> {
+            let command = ControlCommand::parse("open", &mut std::iter::once(uri))
+                .map_err(fdo::Error::InvalidArgs)?
+                .ok_or_else(|| fdo::Error::NotSupported("open is not available".to_string()))?;
+            self.backend.run(command).map_err(failed)
+        }
+
+        #[zbus(signal)]
+        async fn seeked(emitter: &SignalEmitter<'_>, position: i64) -> zbus::Result<()>;
+
+        #[zbus(property)]
+        fn playback_status(&self) -> String {
+            match self.backend.now_playing().state {
+                PlaybackState::Playing => "Playing",
+                PlaybackState::Paused => "Paused",
+                PlaybackState::Stopped => "Stopped",
+            }
+            .to_string()
+        }
+
+        #[zbus(property)]
+        fn metadata(&self) -> HashMap<String, OwnedValue> {
+            metadata(&self.backend.now_playing())
+        }
+
+        #[zbus(property)]
+        fn volume(&self) -> f64 {
+            self.backend.now_playing().volume.unwrap_or(1.0)
+        }
+
+        #[zbus(property)]
+        fn set_volume(&self, volume: f64) -> zbus::Result<()> {
+            let volume = volume.clamp(0.0, 1.0);
+            self.player(PlayerCommand::SetVolume { volume })
+                .map_err(zbus::Error::from)
+        }
+
+        // Clients poll Position; per the spec it does not emit PropertiesChanged.
+        #[zbus(property(emits_changed_signal = "false"))]
+        fn position(&self) -> i64 {
+            now_playing::position_now(&self.backend.now_playing())
+                .map(micros)
+                .unwrap_or(0)
+        }
+
+        #[zbus(property)]
+        fn rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn minimum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn maximum_rate(&self) -> f64 {
+            1.0
+        }
+
+        #[zbus(property)]
+        fn can_go_next(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_go_previous(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_play(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_pause(&self) -> bool {
+            true
+        }
+
+        #[zbus(property)]
+        fn can_seek(&self) -> bool {
+            self.backend.now_playing().duration.is_some()
+        }
+
+        #[zbus(property)]
+        fn can_control(&self) -> bool {
+            true
+        }
+    }
+
+    // Serves both MPRIS interfaces on the given bus. The session bus comes
+    // from DBUS_SESSION_BUS_ADDRESS, so pointing that at a private
+    // dbus-daemon is enough to run the server in isolation.
+    pub fn serve(builder: connection::Builder<'_>, backend: Arc<dyn Backend>) -> zbus::Result<zbus::blocking::Connection> {
+        builder
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, Root { backend: backend.clone() })?
+            .serve_at(OBJECT_PATH, Player { backend })?
+            .build()
+    }
+
+    // Announces changes to clients such as playerctl and desktop media
+    // widgets after the page reports a new now-playing state.
+    fn notify_changed(
+        connection: &zbus::blocking::Connection,
+        previous: &NowPlaying,
+        previous_at: Instant,
+        np: &NowPlaying,
+    ) -> zbus::Result<()> {
+        let player = connection.object_server().interface::<_, Player>(OBJECT_PATH)?;
+        let emitter = player.signal_emitter();
+        let player = player.get();
+
+        zbus::block_on(async {
+            if previous.state != np.state {
+                player.playback_status_changed(emitter).await?;
+            }
+            if metadata(previous) != metadata(np) {
+                player.metadata_changed(emitter).await?;
+                player.can_seek_changed(emitter).await?;
+            }
+            if previous.volume != np.volume {
+                player.volume_changed(emitter).await?;
+            }
+
+            let mut expected = previous.position;
+            if previous.state == PlaybackState::Playing {
+                expected = expected.map(|position| position + previous_at.elapsed().as_secs_f64());
+            }
+            if let (Some(expected), Some(position)) = (expected, np.position)
+                && (position - expected).abs() > SEEK_THRESHOLD_SECS
+                && track_id(previous) == track_id(np) {
+                    Player::seeked(emitter, micros(position)).await?;
+                }
+            Ok(())
+        })
+    }
+
+    pub fn start(app: tauri::AppHandle) {
+        std::thread::spawn(move || {
+            let backend: Arc<dyn Backend> = Arc::new(AppBackend(app.clone()));
+            let builder = match connection::Builder::session() {
+                Ok(builder) => builder,
+                Err(e) => {
+                    eprintln!("MPRIS disabled: failed to connect to the session bus: {}", e);
+                    return;
+                }
+            };
+            let connection = match serve(builder, backend) {
+                Ok(connection) => connection,
+                Err(e) => {
+                    eprintln!("MPRIS disabled: failed to register {}: {}", BUS_NAME, e);
+                    return;
+                }
+            };
+            let connection = CONNECTION.get_or_init(|| connection);
+
+            let previous = Mutex::new((NowPlaying::default(), Instant::now()));
+            app.listen(now_playing::CHANGED_EVENT, move |event| {
+                let Ok(np) = serde_json::from_str::<NowPlaying>(event.payload()) else {
+                    return;
+                };
+                let Ok(mut previous) = previous.lock() else {
+                    return;
+                };
+                if let Err(e) = notify_changed(connection, &previous.0, previous.1, &np) {
+                    eprintln!("Failed to publish MPRIS changes: {}", e);
+                }
+                *previous = (np, Instant::now());
+            });
+        });
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::io::{BufRead, BufReader};
+        use std::process::{Child, Command, Stdio};
+        use zbus::blocking::{Proxy, proxy};
+
+        // A dbus-daemon of its own, so the test neither needs nor disturbs
+        // the desktop's session bus.
+        struct PrivateBus {
+            daemon: Child,
+            address: String,
+        }
+
+        impl PrivateBus {
+            fn start() -> Option<Self> {
+                let mut daemon = match Command::new("dbus-daemon")
+                    .args(["--session", "--nofork", "--print-address=1"])
+                    .stdout(Stdio::piped())
+                    .spawn()
+                {
+                    Ok(daemon) => daemon,
+                    Err(e) => {
+                        eprintln!("Skipping MPRIS test, dbus-daemon is not available: {}", e);
+                        return None;
+                    }
+                };
+                let mut address = String::new();
+                BufReader::new(daemon.stdout.as_mut().unwrap()).read_line(&mut address).unwrap();
+                let bus = PrivateBus { daemon, address: address.trim().to_string() };
+                if bus.address.is_empty() {
+                    eprintln!("Skipping MPRIS test, dbus-daemon did not start");
+                    return None;
+                }
+                Some(bus)
+            }
+
+            fn connect(&self) -> connection::Builder<'static> {
+                connection::Builder::address(self.address.as_str()).unwrap()
+            }
+        }
+
+        impl Drop for PrivateBus {
+            fn drop(&mut self) {
+                let _ = self.daemon.kill();
+                let _ = self.daemon.wait();
+            }
+        }
+
+        struct FakeBackend {
+            np: Mutex<NowPlaying>,
+            commands: Mutex<Vec<ControlCommand>>,
+        }
+
+        impl Backend for FakeBackend {
+            fn run(&self, command: ControlCommand) -> Result<(), String> {
+                self.commands.lock().unwrap().push(command);
+                Ok(())
+            }
+
+            fn now_playing(&self) -> NowPlaying {
+                self.np.lock().unwrap().clone()
+            }
+        }
+
+        fn paused() -> NowPlaying {
+            NowPlaying {
+                title: Some("So What".to_string()),
+                artist: Some("Miles Davis".to_string()),
+                album: Some("Kind of Blue".to_string()),
+                duration: Some(200.0),
+                position: Some(61.5),
+                state: PlaybackState::Paused,
+                volume: Some(0.5),
+                ..NowPlaying::default()
+            }
+        }
+
+        // Starts the server with a fake backend and returns a client
+        // connection on the same private bus.
+        fn setup(bus: &PrivateBus) -> (Arc<FakeBackend>, zbus::blocking::Connection, zbus::blocking::Connection) {
+            let backend = Arc::new(FakeBackend { np: Mutex::new(paused()), commands: Mutex::new(Vec::new()) });
+            let server = serve(bus.connect(), backend.clone()).unwrap();
+            let client = bus.connect().build().unwrap();
+            (backend, server, client)
+        }
+
+        fn interface_proxy<'a>(client: &'a zbus::blocking::Connection, interface: &'static str) -> Proxy<'a> {
+            proxy::Builder::new(client)
+                .destination(BUS_NAME).unwrap()
+                .path(OBJECT_PATH).unwrap()
+                .interface(interface).unwrap()
+                .cache_properties(proxy::CacheProperties::No)
+                .build()
+                .unwrap()
+        }
+
+        #[test]
+        fn properties_reflect_now_playing() {
+            let Some(bus) = PrivateBus::start() else {
+                return;
+            };
+            let (backend, _server, client) = setup(&bus);
+            let player = interface_proxy(&client, "org.mpris.MediaPlayer2.Player");
+            let root = interface_proxy(&client, "org.mpris.MediaPlayer2");
+
+            assert_eq!(root.get_property::<String>("Identity").unwrap(), "Qobuz Player");
+            assert_eq!(player.get_property::<String>("PlaybackStatus").unwrap(), "Paused");
+            assert_eq!(player.get_property::<i64>("Position").unwrap(), 61_500_000);
+            assert_eq!(player.get_property::<f64>("Volume").unwrap(), 0.5);
+            assert!(player.get_property::<bool>("CanSeek").unwrap());
+
+            let metadata = player.get_property::<HashMap<String, OwnedValue>>("Metadata").unwrap();
+            assert_eq!(metadata["xesam:title"].downcast_ref::<&str>().unwrap(), "So What");
+            assert_eq!(metadata["xesam:album"].downcast_ref::<&str>().unwrap(), "Kind of Blue");
+            assert_eq!(metadata["mpris:length"].downcast_ref::<i64>().unwrap(), 200_000_000);
+
+            *backend.np.lock().unwrap() = NowPlaying::default();
+            assert_eq!(player.get_property::<String>("PlaybackStatus").unwrap(), "Stopped");
+            assert!(!player.get_property::<bool>("CanSeek").unwrap());
+            let metadata = player.get_property::<HashMap<String, OwnedValue>>("Metadata").unwrap();
+            assert_eq!(metadata["mpris:trackid"].downcast_ref::<ObjectPath<'_>>().unwrap().as_str(), NO_TRACK);
+        }
+
+        #[test]
+        fn methods_become_control_commands() {
+            let Some(bus) = PrivateBus::start() else {
+                return;
+            };
+            let (backend, _server, client) = setup(&bus);
+            let player = interface_proxy(&client, "org.mpris.MediaPlayer2.Player");
+            let root = interface_proxy(&client, "org.mpris.MediaPlayer2");
+            let current = ObjectPath::try_from(track_id(&paused())).unwrap();
+            let stale = ObjectPath::try_from("/org/qobuz_player/track/0").unwrap();
+
+            player.call_method("PlayPause", &()).unwrap();
+            player.call_method("Next", &()).unwrap();
+            player.call_method("Seek", &(10_000_000i64)).unwrap();
+            // Past the end of the track, which skips to the next one.
+            player.call_method("Seek", &(500_000_000i64)).unwrap();
+            player.call_method("SetPosition", &(&current, 5_000_000i64)).unwrap();
+            player.call_method("SetPosition", &(&stale, 5_000_000i64)).unwrap();
+            player.set_property("Volume", 1.5f64).unwrap();
+            root.call_method("Raise", &()).unwrap();
+
+            assert_eq!(*backend.commands.lock().unwrap(), vec![
+                ControlCommand::Player(PlayerCommand::Toggle),
+                ControlCommand::Player(PlayerCommand::Next),
+                ControlCommand::Player(PlayerCommand::Seek { position: 71.5 }),
+                ControlCommand::Player(PlayerCommand::Next),
+                ControlCommand::Player(PlayerCommand::Seek { position: 5.0 }),
+                ControlCommand::Player(PlayerCommand::SetVolume { volume: 1.0 }),
+                ControlCommand::Show,
+            ]);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use linux_impl::*;
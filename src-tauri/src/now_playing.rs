@@ -0,0 +1,162 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Instant;
+use tauri::{Emitter, Manager};
+
+pub const CHANGED_EVENT: &str = "now-playing-changed";
+
+// When the stored NowPlaying was reported, to advance its position between
+// heartbeats.
+static REPORTED_AT: Mutex<Option<Instant>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackState {
+    #[default]
+    Stopped,
+    Playing,
+    Paused,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NowPlaying {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub cover_url: Option<String>,
+    // Seconds.
+    pub duration: Option<f64>,
+    pub position: Option<f64>,
+    pub state: PlaybackState,
+    pub quality: Option<String>,
+    // 0.0 to 1.0.
+    pub volume: Option<f64>,
+}
+
+// Injected into every page the main window loads. Reads the track from the
+// Media Session metadata Qobuz publishes, falling back to the player bar, and
+// reports to Rust once DOM and <audio> events settle. While playing, a
+// heartbeat keeps the position reasonably fresh without a report per
+// timeupdate.
+pub const OBSERVER_JS: &str = r#"
+(function() {
+    if (window.__qobuzNowPlayingObserver) return;
+    window.__qobuzNowPlayingObserver = true;
+
+    const DEBOUNCE_MS = 250;
+    const HEARTBEAT_MS = 5000;
+
+    const text = selectors => {
+        for (const s of selectors) {
+            const el = document.querySelector(s);
+            const value = el && el.textContent.trim();
+            if (value) return value;
+        }
+        return null;
+    };
+    const finite = value => Number.isFinite(value) ? value : null;
+
+    function snapshot() {
+        const media = document.querySelector('audio, video');
+        const meta = navigator.mediaSession ? navigator.mediaSession.metadata : null;
+        const artwork = meta && meta.artwork && meta.artwork.length > 0
+            ? meta.artwork[meta.artwork.length - 1].src
+            : null;
+        const cover = document.querySelector('.player__track-cover img, .pct-player-cover img, [data-testid*="cover"] img');
+
+        let state = 'stopped';
+        if (media && (media.currentSrc || media.src)) {
+            state = media.paused ? 'paused' : 'playing';
+        }
+
+        return {
+            title: (meta && meta.title) || text(['.player__track-name', '.pct-player-track-title', '[data-testid*="track-title"]']),
+            artist: (meta && meta.artist) || text(['.player__track-album .player__track-artist', '.pct-player-artist', '[data-testid*="track-artist"]']),
+            album: (meta && meta.album) || text(['.player__track-album-name', '.pct-player-album', '[data-testid*="track-album"]']),
+            cover_url: artwork || (cover && cover.src) || null,
+            duration: media ? finite(media.duration) : null,
+            position: media ? finite(media.currentTime) : null,
+            state,
+            quality: text(['.player__track-quality', '.pct-player-quality', '[data-testid*="quality"]']),
+            volume: media ? finite(media.volume) : null
+        };
+    }
+
+    let timer = null;
+    let last = null;
+
+    function report(force) {
+        if (!window.__TAURI__ || !window.__TAURI__.core) return;
+        const current = snapshot();
+        const key = JSON.stringify({ ...current, position: null });
+        if (!force && key === last) return;
+        last = key;
+        window.__TAURI__.core.invoke('report_now_playing', { nowPlaying: current }).catch(() => {});
+    }
+
+    function schedule(force) {
+        clearTimeout(timer);
+        timer = setTimeout(() => report(force), DEBOUNCE_MS);
+    }
+
+    // Media events do not bubble, but they can be captured on the document,
+    // which also covers <audio> elements the player creates later.
+    ['play', 'pause', 'ended', 'emptied', 'loadedmetadata', 'durationchange', 'volumechange'].forEach(type => {
+        document.addEventListener(type, () => schedule(false), true);
+    });
+    document.addEventListener('seeked', () => schedule(true), true);
+
+    function observe() {
+        new MutationObserver(() => schedule(false)).observe(document.body, {
+            childList: true,
+            subtree: true,
+            characterData: true
+        });
+        schedule(true);
+    }
+    if (document.body) {
+        observe();
+    } else {
+        document.addEventListener('DOMContentLoaded', observe);
+    }
+
+    setInterval(() => {
+        const media = document.querySelector('audio, video');
+        if (media && !media.paused) report(true);
+    }, HEARTBEAT_MS);
+})();
+"#;
+
+// Stores a report from the page and tells the rest of the app about it.
+// Subsystems follow playback by listening for CHANGED_EVENT.
+pub fn update(app: &tauri::AppHandle, now_playing: NowPlaying) -> Result<(), String> {
+    let state = app.state::<crate::AppState>();
+    {
+        let mut current = state.now_playing.lock()
+            .map_err(|e| format!("Failed to lock now playing: {}", e))?;
+        if *current == now_playing {
+            return Ok(());
+        }
+        *current = now_playing.clone();
+        if let Ok(mut reported_at) = REPORTED_AT.lock() {
+            *reported_at = Some(Instant::now());
+        }
+    }
+
+    app.emit(CHANGED_EVENT, &now_playing)
+        .map_err(|e| format!("Failed to emit {}: {}", CHANGED_EVENT, e))
+}
+
+// The last reported position, advanced by the time spent playing since.
+pub fn position_now(now_playing: &NowPlaying) -> Option<f64> {
+    let position = now_playing.position?;
+    if now_playing.state != PlaybackState::Playing {
+        return Some(position);
+    }
+    let elapsed = REPORTED_AT.lock().ok()
+        .and_then(|reported_at| reported_at.map(|at| at.elapsed().as_secs_f64()))
+        .unwrap_or(0.0);
+    let position = position + elapsed;
+    Some(now_playing.duration.map_or(position, |duration| position.min(duration)))
+}
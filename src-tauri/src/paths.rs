@@ -0,0 +1,127 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const APP_DIR_NAME: &str = "qobuz-player";
+const CONFIG_DIR_ENV: &str = "QOBUZ_PLAYER_CONFIG_DIR";
+const PORTABLE_MARKER: &str = "portable";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigDirSource {
+    CommandLine,
+    Environment,
+    Portable,
+    Default,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AppPaths {
+    pub config_dir: PathBuf,
+    pub config_dir_source: ConfigDirSource,
+    pub cache_dir: PathBuf,
+    // None keeps the webview's own per-user default location.
+    pub webview_data_dir: Option<PathBuf>,
+}
+
+static APP_PATHS: OnceLock<AppPaths> = OnceLock::new();
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+}
+
+fn resolve(cli_config_dir: Option<PathBuf>) -> Result<AppPaths, String> {
+    let portable_root = exe_dir().filter(|dir| dir.join(PORTABLE_MARKER).is_file());
+
+    let (config_dir, config_dir_source) = if let Some(dir) = cli_config_dir {
+        (dir, ConfigDirSource::CommandLine)
+    } else if let Some(dir) = std::env::var_os(CONFIG_DIR_ENV).filter(|v| !v.is_empty()) {
+        (PathBuf::from(dir), ConfigDirSource::Environment)
+    } else if let Some(root) = &portable_root {
+        (root.join("config"), ConfigDirSource::Portable)
+    } else {
+        let dir = dirs::config_dir()
+            .ok_or_else(|| "Could not determine config directory".to_string())?;
+        (dir.join(APP_DIR_NAME), ConfigDirSource::Default)
+    };
+
+    let config_dir = if config_dir.is_relative() {
+        std::env::current_dir()
+            .map_err(|e| format!("Failed to resolve config directory: {}", e))?
+            .join(config_dir)
+    } else {
+        config_dir
+    };
+
+    let paths = match portable_root {
+        Some(root) => AppPaths {
+            config_dir,
+            config_dir_source,
+            cache_dir: root.join("cache"),
+            webview_data_dir: Some(root.join("webview")),
+        },
+        None => {
+            let cache_dir = dirs::cache_dir()
+                .map(|d| d.join(APP_DIR_NAME))
+                .unwrap_or_else(|| config_dir.join("cache"));
+            AppPaths {
+                config_dir,
+                config_dir_source,
+                cache_dir,
+                webview_data_dir: None,
+            }
+        }
+    };
+
+    Ok(paths)
+}
+
+// Must run before anything reads a path; later calls are ignored.
+pub fn init(cli_config_dir: Option<PathBuf>) -> Result<&'static AppPaths, String> {
+    if let Some(paths) = APP_PATHS.get() {
+        return Ok(paths);
+    }
+    let paths = resolve(cli_config_dir)?;
+    Ok(APP_PATHS.get_or_init(|| paths))
+}
+
+pub fn get() -> Result<&'static AppPaths, String> {
+    init(None)
+}
+
+fn ensure_dir(dir: &Path) -> Result<PathBuf, String> {
+    if !dir.exists() {
+        fs::create_dir_all(dir)
+            .map_err(|e| format!("Failed to create directory {}: {}", dir.display(), e))?;
+    }
+    Ok(dir.to_path_buf())
+}
+
+pub fn config_dir() -> Result<PathBuf, String> {
+    ensure_dir(&get()?.config_dir)
+}
+
+pub fn cache_dir() -> Result<PathBuf, String> {
+    ensure_dir(&get()?.cache_dir)
+}
+
+pub fn webview_data_dir() -> Result<Option<PathBuf>, String> {
+    match &get()?.webview_data_dir {
+        Some(dir) => ensure_dir(dir).map(Some),
+        None => Ok(None),
+    }
+}
+
+// The config directory relaunches (autostart) have to be given explicitly.
+// A portable or default directory is found again on its own, but neither
+// --config-dir nor QOBUZ_PLAYER_CONFIG_DIR is there at login: the login
+// session usually does not have the variable set.
+pub fn config_dir_launch_arg() -> Option<PathBuf> {
+    get()
+        .ok()
+        .filter(|p| matches!(p.config_dir_source, ConfigDirSource::CommandLine | ConfigDirSource::Environment))
+        .map(|p| p.config_dir.clone())
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use tauri::Manager;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum PlayerCommand {
+    Play,
+    Pause,
+    Toggle,
+    Next,
+    Previous,
+    Seek { position: f64 },
+    SetVolume { volume: f64 },
+    ToggleShuffle,
+    CycleRepeat,
+    ToggleFavorite,
+}
+
+impl PlayerCommand {
+    pub fn action(&self) -> &'static str {
+        match self {
+            PlayerCommand::Play => "play",
+            PlayerCommand::Pause => "pause",
+            PlayerCommand::Toggle => "toggle",
+            PlayerCommand::Next => "next",
+            PlayerCommand::Previous => "previous",
+            PlayerCommand::Seek { .. } => "seek",
+            PlayerCommand::SetVolume { .. } => "set_volume",
+            PlayerCommand::ToggleShuffle => "toggle_shuffle",
+            PlayerCommand::CycleRepeat => "cycle_repeat",
+            PlayerCommand::ToggleFavorite => "toggle_favorite",
+        }
+    }
+
+    fn argument(&self) -> Option<f64> {
+        match self {
+            PlayerCommand::Seek { position } => Some(position.max(0.0)),
+            PlayerCommand::SetVolume { volume } => Some(volume.clamp(0.0, 1.0)),
+            _ => None,
+        }
+    }
+}
+
+const DISPATCH_JS: &str = r#"
+(function(action, arg, selectors) {
+    const media = document.querySelector('audio, video');
+    const click = () => {
+        // Selectors come from the selector pack and may be user-edited,
+        // so an invalid one is skipped rather than aborting the command.
+        for (const s of selectors) {
+            let el = null;
+            try { el = document.querySelector(s); } catch (e) { continue; }
+            if (el) { el.click(); return; }
+        }
+    };
+    switch (action) {
+        case 'play':
+            if (media) { media.play(); } else { click(); }
+            break;
+        case 'pause':
+            if (media) { media.pause(); } else { click(); }
+            break;
+        case 'toggle':
+            if (media) { if (media.paused) media.play(); else media.pause(); } else { click(); }
+            break;
+        case 'seek':
+            if (media) { media.currentTime = Math.min(arg, media.duration || arg); }
+            break;
+        case 'set_volume':
+            if (media) { media.volume = arg; }
+            break;
+        default:
+            click();
+    }
+})"#;
+
+pub fn script(command: &PlayerCommand) -> String {
+    let action = command.action();
+    let arg = command.argument().map(|v| v.to_string()).unwrap_or_else(|| "null".to_string());
+    let selectors = serde_json::to_string(&crate::selectors::for_action(action))
+        .unwrap_or_else(|_| "[]".to_string());
+    format!("{}('{}', {}, {});", DISPATCH_JS, action, arg, selectors)
+}
+
+pub fn dispatch(app: &tauri::AppHandle, command: &PlayerCommand) -> Result<(), String> {
+    let window = app.get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+    window.eval(&script(command))
+        .map_err(|e| format!("Failed to run player command: {}", e))
+}
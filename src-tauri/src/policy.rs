@@ -0,0 +1,216 @@
+use crate::settings::Settings;
+use serde_json::{Map, Value};
+use std::fs;
+use std::path::PathBuf;
+
+const POLICY_PATH_ENV: &str = "QOBUZ_PLAYER_POLICY";
+
+// A policy file is a JSON object mapping settings.json keys to the value the
+// administrator enforces, e.g. { "close_to_tray": false }. Objects lock only
+// the keys they list, so { "remote_api": { "enabled": false } } leaves the
+// port and token up to the user.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    locked: Map<String, Value>,
+}
+
+fn default_policy_path() -> Option<PathBuf> {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("ProgramData")
+            .map(|dir| PathBuf::from(dir).join("qobuz-player").join("policy.json"))
+    }
+    #[cfg(target_os = "macos")]
+    {
+        Some(PathBuf::from("/Library/Application Support/qobuz-player/policy.json"))
+    }
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    {
+        Some(PathBuf::from("/etc/qobuz-player/policy.json"))
+    }
+}
+
+impl Policy {
+    pub fn path() -> Option<PathBuf> {
+        std::env::var_os(POLICY_PATH_ENV)
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from)
+            .or_else(default_policy_path)
+    }
+
+    pub fn load() -> Result<Self, String> {
+        let Some(path) = Self::path() else {
+            return Ok(Policy::default());
+        };
+        if !path.exists() {
+            return Ok(Policy::default());
+        }
+
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read policy file {}: {}", path.display(), e))?;
+        Self::from_json(&contents)
+            .map_err(|e| format!("Invalid policy file {}: {}", path.display(), e))
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self, String> {
+        let locked = match serde_json::from_str::<Value>(contents) {
+            Ok(Value::Object(map)) => map,
+            Ok(_) => return Err("Policy must be a JSON object".to_string()),
+            Err(e) => return Err(format!("Failed to parse policy: {}", e)),
+        };
+
+        let known = Settings::default().to_map()?;
+        for key in locked.keys() {
+            if key == "version" || !known.contains_key(key) {
+                return Err(format!("Unknown setting \"{}\"", key));
+            }
+        }
+
+        // A nested key the settings do not have is dropped when applied, so
+        // it shows up as a mismatch here.
+        let policy = Policy { locked };
+        let applied = policy.apply(&Settings::default())?.to_map()?;
+        if let Some(key) = mismatch(&policy.locked, &applied, "") {
+            return Err(format!("Unknown setting \"{}\"", key));
+        }
+        Ok(policy)
+    }
+
+    // Dotted paths of the locked values, e.g. "remote_api.enabled".
+    pub fn locked_fields(&self) -> Vec<String> {
+        let mut paths = Vec::new();
+        leaf_paths(&self.locked, "", &mut paths);
+        paths
+    }
+
+    pub fn apply(&self, settings: &Settings) -> Result<Settings, String> {
+        if self.locked.is_empty() {
+            return Ok(settings.clone());
+        }
+
+        let mut map = settings.to_map()?;
+        merge(&mut map, &self.locked);
+        Settings::from_map(map)
+            .map_err(|e| format!("Policy value does not fit settings: {}", e))
+    }
+
+    // Puts the user's own value from `file` back in place of every locked
+    // value in `map`, so enforced values never end up in settings.json.
+    pub fn restore_file_values(&self, map: &mut Map<String, Value>, file: &Map<String, Value>) {
+        restore(&self.locked, map, file);
+    }
+
+    pub fn check(&self, settings: &Settings) -> Result<(), String> {
+        match mismatch(&self.locked, &settings.to_map()?, "") {
+            Some(key) => Err(format!("\"{}\" is locked by the administrator policy", key)),
+            None => Ok(()),
+        }
+    }
+}
+
+// Lays `overrides` over `map`, descending into objects so only the keys an
+// override lists are replaced. Also used for the environment and --set layers.
+pub fn merge(map: &mut Map<String, Value>, overrides: &Map<String, Value>) {
+    for (key, value) in overrides {
+        match (map.get_mut(key), value) {
+            (Some(Value::Object(current)), Value::Object(overrides)) => merge(current, overrides),
+            _ => {
+                map.insert(key.clone(), value.clone());
+            }
+        }
+    }
+}
+
+// Undoes `merge`: every key `overrides` lists gets its value from `file`.
+pub fn restore(overrides: &Map<String, Value>, map: &mut Map<String, Value>, file: &Map<String, Value>) {
+    for (key, value) in overrides {
+        match (value, map.get_mut(key), file.get(key)) {
+            (Value::Object(overrides), Some(Value::Object(current)), Some(Value::Object(file))) => {
+                restore(overrides, current, file)
+            }
+            (_, _, Some(file)) => {
+                map.insert(key.clone(), file.clone());
+            }
+            (_, _, None) => {
+                map.remove(key);
+            }
+        }
+    }
+}
+
+// Dotted paths of the non-object values in `map`; an empty object counts as
+// a value of its own.
+pub fn leaf_paths(map: &Map<String, Value>, prefix: &str, paths: &mut Vec<String>) {
+    for (key, value) in map {
+        let path = format!("{}{}", prefix, key);
+        match value {
+            Value::Object(inner) if !inner.is_empty() => leaf_paths(inner, &format!("{}.", path), paths),
+            _ => paths.push(path),
+        }
+    }
+}
+
+// The dotted path of the first locked leaf that `map` does not match.
+pub fn mismatch(locked: &Map<String, Value>, map: &Map<String, Value>, prefix: &str) -> Option<String> {
+    locked.iter().find_map(|(key, value)| {
+        let path = format!("{}{}", prefix, key);
+        match (value, map.get(key)) {
+            (Value::Object(locked), Some(Value::Object(current))) => {
+                mismatch(locked, current, &format!("{}.", path))
+            }
+            (value, current) if current == Some(value) => None,
+            _ => Some(path),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nested_lock_only_covers_listed_keys() {
+        let policy = Policy::from_json(r#"{ "remote_api": { "enabled": false } }"#).unwrap();
+        let mut settings = Settings::default();
+        settings.remote_api.port = 9000;
+        assert!(policy.check(&settings).is_ok());
+
+        let applied = policy.apply(&settings).unwrap();
+        assert_eq!(applied.remote_api.port, 9000);
+
+        settings.remote_api.enabled = true;
+        assert_eq!(
+            policy.check(&settings).unwrap_err(),
+            "\"remote_api.enabled\" is locked by the administrator policy"
+        );
+    }
+
+    #[test]
+    fn locked_fields_are_dotted_leaf_paths() {
+        let policy = Policy::from_json(r#"{ "close_to_tray": false, "remote_api": { "enabled": false } }"#).unwrap();
+        assert_eq!(policy.locked_fields(), vec!["close_to_tray", "remote_api.enabled"]);
+    }
+
+    #[test]
+    fn locked_values_are_replaced_by_the_file_values() {
+        let policy = Policy::from_json(r#"{ "close_to_tray": false, "remote_api": { "enabled": false } }"#).unwrap();
+        let mut file = Settings::default();
+        file.close_to_tray = true;
+        file.remote_api.enabled = true;
+        let mut edited = policy.apply(&file).unwrap();
+        edited.remote_api.port = 9000;
+
+        let mut map = edited.to_map().unwrap();
+        policy.restore_file_values(&mut map, &file.to_map().unwrap());
+        let saved = Settings::from_map(map).unwrap();
+        assert!(saved.close_to_tray);
+        assert!(saved.remote_api.enabled);
+        assert_eq!(saved.remote_api.port, 9000);
+    }
+
+    #[test]
+    fn unknown_nested_key_is_rejected() {
+        let error = Policy::from_json(r#"{ "remote_api": { "enable": false } }"#).unwrap_err();
+        assert_eq!(error, "Unknown setting \"remote_api.enable\"");
+    }
+}
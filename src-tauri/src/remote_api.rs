@@ -0,0 +1,443 @@
+use crate::now_playing::{self, NowPlaying};
+use crate::player::PlayerCommand;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, header};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::async_runtime::JoinHandle;
+use tauri::{Listener, Manager};
+use tokio::sync::{broadcast, watch};
+
+const OPENAPI_JSON: &str = include_str!("../openapi.json");
+const MIN_TOKEN_LEN: usize = 16;
+// Now-playing events a slow WebSocket client may fall behind by before it
+// skips ahead.
+const EVENT_BUFFER: usize = 16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RemoteApiSettings {
+    pub enabled: bool,
+    // 127.0.0.1 keeps the API to this machine; 0.0.0.0 or a LAN address
+    // opens it to other devices.
+    pub bind_address: String,
+    pub port: u16,
+    pub token: String,
+}
+
+impl Default for RemoteApiSettings {
+    fn default() -> Self {
+        RemoteApiSettings {
+            enabled: false,
+            bind_address: "127.0.0.1".to_string(),
+            port: 8787,
+            token: String::new(),
+        }
+    }
+}
+
+impl RemoteApiSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.bind_address.parse::<IpAddr>()
+            .map_err(|e| format!("Invalid remote API bind address \"{}\": {}", self.bind_address, e))?;
+        if self.port == 0 {
+            return Err("Remote API port must not be 0".to_string());
+        }
+        // Tokens also travel in the WebSocket URL, so keep them URL-safe.
+        if self.token.len() < MIN_TOKEN_LEN
+            || !self.token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!(
+                "Remote API token must be at least {} characters of letters, digits, - and _",
+                MIN_TOKEN_LEN
+            ));
+        }
+        Ok(())
+    }
+}
+
+// What the handlers need from the app. Kept narrow so the tests below can
+// drive the router without a webview.
+trait Backend: Send + Sync + 'static {
+    fn run(&self, command: PlayerCommand) -> Result<(), String>;
+    fn now_playing(&self) -> NowPlaying;
+}
+
+struct AppBackend(tauri::AppHandle);
+
+impl Backend for AppBackend {
+    // Every action goes through the same dispatcher as the thumbnail toolbar.
+    fn run(&self, command: PlayerCommand) -> Result<(), String> {
+        crate::player::dispatch(&self.0, &command)
+    }
+
+    fn now_playing(&self) -> NowPlaying {
+        let state = self.0.state::<crate::AppState>();
+        state.now_playing.lock().map(|np| np.clone()).unwrap_or_default()
+    }
+}
+
+#[derive(Clone)]
+struct ApiState {
+    backend: Arc<dyn Backend>,
+    token: Arc<str>,
+    events: broadcast::Sender<String>,
+    stop: watch::Receiver<bool>,
+}
+
+// The running server's settings, the switch that stops it and its task.
+struct Server {
+    settings: RemoteApiSettings,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+static SERVER: Mutex<Option<Server>> = Mutex::new(None);
+static EVENTS: OnceLock<broadcast::Sender<String>> = OnceLock::new();
+
+fn events() -> &'static broadcast::Sender<String> {
+    EVENTS.get_or_init(|| broadcast::channel(EVENT_BUFFER).0)
+}
+
+fn tokens_match(given: &str, expected: &str) -> bool {
+    // Compares every byte so the time taken does not give the token away.
+    given.len() == expected.len()
+        && given.bytes().zip(expected.bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+// Accepts "Authorization: Bearer <token>", or ?token=<token> for WebSocket
+// clients such as browsers that cannot set headers.
+async fn authorize(State(state): State<ApiState>, request: Request, next: Next) -> Response {
+    let from_header = request.headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    let from_query = request.uri()
+        .query()
+        .and_then(|query| query.split('&').find_map(|pair| pair.strip_prefix("token=")));
+
+    match from_header.or(from_query) {
+        Some(token) if tokens_match(token.trim(), &state.token) => next.run(request).await,
+        _ => error(StatusCode::UNAUTHORIZED, "Missing or invalid token"),
+    }
+}
+
+fn error(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn current_status(state: &ApiState) -> NowPlaying {
+    let np = state.backend.now_playing();
+    NowPlaying { position: now_playing::position_now(&np), ..np }
+}
+
+fn run(state: &ApiState, command: PlayerCommand) -> Response {
+    match state.backend.run(command) {
+        Ok(()) => StatusCode::NO_CONTENT.into_response(),
+        Err(e) => error(StatusCode::INTERNAL_SERVER_ERROR, &e),
+    }
+}
+
+async fn status(State(state): State<ApiState>) -> Json<NowPlaying> {
+    Json(current_status(&state))
+}
+
+async fn play_pause(State(state): State<ApiState>) -> Response {
+    run(&state, PlayerCommand::Toggle)
+}
+
+async fn next_track(State(state): State<ApiState>) -> Response {
+    run(&state, PlayerCommand::Next)
+}
+
+async fn previous_track(State(state): State<ApiState>) -> Response {
+    run(&state, PlayerCommand::Previous)
+}
+
+#[derive(Deserialize)]
+struct VolumeRequest {
+    volume: f64,
+}
+
+async fn volume(State(state): State<ApiState>, request: Result<Json<VolumeRequest>, JsonRejection>) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.body_text()),
+    };
+    if !(0.0..=1.0).contains(&request.volume) {
+        return error(StatusCode::BAD_REQUEST, "volume must be between 0.0 and 1.0");
+    }
+    run(&state, PlayerCommand::SetVolume { volume: request.volume })
+}
+
+#[derive(Deserialize)]
+struct SeekRequest {
+    position: f64,
+}
+
+async fn seek(State(state): State<ApiState>, request: Result<Json<SeekRequest>, JsonRejection>) -> Response {
+    let Json(request) = match request {
+        Ok(request) => request,
+        Err(e) => return error(StatusCode::BAD_REQUEST, &e.body_text()),
+    };
+    if !request.position.is_finite() || request.position < 0.0 {
+        return error(StatusCode::BAD_REQUEST, "position must be a number of seconds, 0 or more");
+    }
+    run(&state, PlayerCommand::Seek { position: request.position })
+}
+
+async fn openapi() -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], OPENAPI_JSON).into_response()
+}
+
+async fn websocket(State(state): State<ApiState>, upgrade: WebSocketUpgrade) -> Response {
+    upgrade.on_upgrade(move |socket| stream_events(socket, state))
+}
+
+// Sends the current state, then every now-playing change, until the client
+// goes away or the server is stopped.
+async fn stream_events(mut socket: WebSocket, mut state: ApiState) {
+    let mut events = state.events.subscribe();
+    let current = serde_json::to_string(&current_status(&state)).unwrap_or_default();
+    if socket.send(Message::Text(current.into())).await.is_err() {
+        return;
+    }
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Ok(payload) => {
+                    if socket.send(Message::Text(payload.into())).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(_)) => {}
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+            _ = state.stop.wait_for(|stop| *stop) => {
+                let _ = socket.send(Message::Close(None)).await;
+                return;
+            }
+        }
+    }
+}
+
+fn router(state: ApiState) -> Router {
+    let api = Router::new()
+        .route("/status", get(status))
+        .route("/play-pause", post(play_pause))
+        .route("/next", post(next_track))
+        .route("/previous", post(previous_track))
+        .route("/volume", post(volume))
+        .route("/seek", post(seek))
+        .route("/events", get(websocket))
+        .route_layer(middleware::from_fn_with_state(state.clone(), authorize));
+
+    Router::new()
+        .route("/openapi.json", get(openapi))
+        .merge(api)
+        .with_state(state)
+}
+
+// Starts, restarts or stops the server to match the settings. Changing the
+// address, port or token closes existing connections.
+pub fn apply(app: &tauri::AppHandle) {
+    let settings = {
+        let state = app.state::<crate::AppState>();
+        state.settings.lock().map(|s| s.remote_api.clone()).unwrap_or_default()
+    };
+    let Ok(mut server) = SERVER.lock() else {
+        return;
+    };
+    if server.as_ref().is_some_and(|running| running.settings == settings) {
+        return;
+    }
+    // The old server still holds the port until its task finishes.
+    let previous = server.take().map(|old| {
+        let _ = old.stop.send(true);
+        old.task
+    });
+    if !settings.enabled {
+        return;
+    }
+    if let Err(e) = settings.validate() {
+        eprintln!("Remote control API disabled: {}", e);
+        return;
+    }
+    let Ok(ip) = settings.bind_address.parse::<IpAddr>() else {
+        return;
+    };
+    let address = SocketAddr::new(ip, settings.port);
+
+    let (stop_sender, stop) = watch::channel(false);
+    let state = ApiState {
+        backend: Arc::new(AppBackend(app.clone())),
+        token: settings.token.as_str().into(),
+        events: events().clone(),
+        stop: stop.clone(),
+    };
+    let task = tauri::async_runtime::spawn(async move {
+        if let Some(previous) = previous {
+            let _ = previous.await;
+        }
+        let listener = match tokio::net::TcpListener::bind(address).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                eprintln!("Remote control API disabled: failed to listen on {}: {}", address, e);
+                return;
+            }
+        };
+        let mut stop = stop;
+        let shutdown = async move {
+            let _ = stop.wait_for(|stop| *stop).await;
+        };
+        if let Err(e) = axum::serve(listener, router(state)).with_graceful_shutdown(shutdown).await {
+            eprintln!("Remote control API stopped: {}", e);
+        }
+    });
+    *server = Some(Server { settings, stop: stop_sender, task });
+}
+
+pub fn start(app: tauri::AppHandle) {
+    app.listen(now_playing::CHANGED_EVENT, |event| {
+        // No subscribers just means no WebSocket clients are connected.
+        let _ = events().send(event.payload().to_string());
+    });
+    let handle = app.clone();
+    app.listen("settings-changed", move |_| apply(&handle));
+    apply(&app);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use tower::ServiceExt;
+
+    const TOKEN: &str = "test-token-0123456789";
+
+    #[derive(Default)]
+    struct FakeBackend {
+        commands: Mutex<Vec<PlayerCommand>>,
+    }
+
+    impl Backend for FakeBackend {
+        fn run(&self, command: PlayerCommand) -> Result<(), String> {
+            self.commands.lock().unwrap().push(command);
+            Ok(())
+        }
+
+        fn now_playing(&self) -> NowPlaying {
+            NowPlaying::default()
+        }
+    }
+
+    fn setup() -> (Arc<FakeBackend>, Router) {
+        let backend = Arc::new(FakeBackend::default());
+        let (_, stop) = watch::channel(false);
+        let state = ApiState {
+            backend: backend.clone(),
+            token: TOKEN.into(),
+            events: broadcast::channel(EVENT_BUFFER).0,
+            stop,
+        };
+        (backend, router(state))
+    }
+
+    async fn send(router: &Router, method: &str, uri: &str, authorization: Option<&str>, body: &str) -> StatusCode {
+        let mut request = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        let request = request.body(Body::from(body.to_string())).unwrap();
+        router.clone().oneshot(request).await.unwrap().status()
+    }
+
+    fn bearer() -> String {
+        format!("Bearer {}", TOKEN)
+    }
+
+    #[tokio::test]
+    async fn missing_or_wrong_token_is_unauthorized() {
+        let (backend, router) = setup();
+
+        assert_eq!(send(&router, "GET", "/status", None, "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&router, "GET", "/status", Some("Bearer wrong-token-0123456789"), "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&router, "GET", "/status", Some(TOKEN), "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&router, "GET", "/status?token=wrong-token-0123456789", None, "").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&router, "POST", "/play-pause", None, "").await, StatusCode::UNAUTHORIZED);
+        assert!(backend.commands.lock().unwrap().is_empty());
+
+        // The API description is public so clients can discover the routes.
+        assert_eq!(send(&router, "GET", "/openapi.json", None, "").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn bearer_header_and_query_token_are_accepted() {
+        let (backend, router) = setup();
+
+        assert_eq!(send(&router, "GET", "/status", Some(&bearer()), "").await, StatusCode::OK);
+        assert_eq!(send(&router, "GET", &format!("/status?token={}", TOKEN), None, "").await, StatusCode::OK);
+        assert_eq!(send(&router, "POST", "/play-pause", Some(&bearer()), "").await, StatusCode::NO_CONTENT);
+        assert_eq!(send(&router, "POST", &format!("/next?token={}", TOKEN), None, "").await, StatusCode::NO_CONTENT);
+        assert_eq!(*backend.commands.lock().unwrap(), vec![PlayerCommand::Toggle, PlayerCommand::Next]);
+    }
+
+    #[tokio::test]
+    async fn volume_and_seek_reject_bad_input() {
+        let (backend, router) = setup();
+        let auth = bearer();
+
+        for body in [r#"{"volume": 1.5}"#, r#"{"volume": -0.1}"#, r#"{"volume": NaN}"#, r#"{"volume": "loud"}"#, "{}"] {
+            assert_eq!(send(&router, "POST", "/volume", Some(&auth), body).await, StatusCode::BAD_REQUEST, "{}", body);
+        }
+        for body in [r#"{"position": -1}"#, r#"{"position": NaN}"#, r#"{"position": 1e400}"#, "{}"] {
+            assert_eq!(send(&router, "POST", "/seek", Some(&auth), body).await, StatusCode::BAD_REQUEST, "{}", body);
+        }
+        assert!(backend.commands.lock().unwrap().is_empty());
+
+        assert_eq!(send(&router, "POST", "/volume", Some(&auth), r#"{"volume": 0.5}"#).await, StatusCode::NO_CONTENT);
+        assert_eq!(send(&router, "POST", "/seek", Some(&auth), r#"{"position": 30}"#).await, StatusCode::NO_CONTENT);
+        assert_eq!(
+            *backend.commands.lock().unwrap(),
+            vec![PlayerCommand::SetVolume { volume: 0.5 }, PlayerCommand::Seek { position: 30.0 }]
+        );
+    }
+
+    #[test]
+    fn validate_checks_the_token() {
+        let enabled = |token: &str| RemoteApiSettings {
+            enabled: true,
+            token: token.to_string(),
+            ..RemoteApiSettings::default()
+        };
+
+        // A disabled server needs no token.
+        assert!(RemoteApiSettings::default().validate().is_ok());
+        assert!(enabled("abcdefghij-_0123").validate().is_ok());
+        assert!(enabled("").validate().is_err());
+        assert!(enabled("abcdefghij-_012").validate().is_err());
+        assert!(enabled("abcdefghij 0123456").validate().is_err());
+        assert!(enabled("abcdefghij&token=x").validate().is_err());
+        assert!(enabled("abcdéfghij0123456").validate().is_err());
+        assert!(RemoteApiSettings { port: 0, ..enabled(TOKEN) }.validate().is_err());
+        assert!(RemoteApiSettings { bind_address: "localhost".to_string(), ..enabled(TOKEN) }.validate().is_err());
+    }
+}
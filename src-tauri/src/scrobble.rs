@@ -0,0 +1,347 @@
+use crate::listens::{self, Listen, ListenService, SubmitError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+pub const DEFAULT_API_URL: &str = "https://ws.audioscrobbler.com/2.0/";
+const QUEUE_FILE: &str = "scrobble-queue.json";
+// track.scrobble accepts at most this many tracks per request.
+const BATCH_SIZE: usize = 50;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+// Where and as whom to scrobble. Any server speaking the Last.fm 2.0 API
+// works, e.g. Libre.fm at https://libre.fm/2.0/. The password is only used
+// to get the session key and is never stored.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScrobbleSettings {
+    pub enabled: bool,
+    pub api_url: String,
+    pub api_key: String,
+    pub api_secret: String,
+    pub username: String,
+    pub session_key: String,
+}
+
+impl Default for ScrobbleSettings {
+    fn default() -> Self {
+        ScrobbleSettings {
+            enabled: false,
+            api_url: DEFAULT_API_URL.to_string(),
+            api_key: String::new(),
+            api_secret: String::new(),
+            username: String::new(),
+            session_key: String::new(),
+        }
+    }
+}
+
+impl ScrobbleSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.enabled {
+            return Ok(());
+        }
+        self.check_server()?;
+        if self.session_key.is_empty() {
+            return Err("Scrobbling is enabled but not logged in; enter the password to log in".to_string());
+        }
+        Ok(())
+    }
+
+    fn check_server(&self) -> Result<(), String> {
+        if !self.api_url.starts_with("https://") && !self.api_url.starts_with("http://") {
+            return Err(format!("Scrobbling API URL must start with http:// or https://, got \"{}\"", self.api_url));
+        }
+        for (name, value) in [("api_key", &self.api_key), ("api_secret", &self.api_secret)] {
+            if value.trim().is_empty() {
+                return Err(format!("Scrobbling is enabled but {} is empty", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Exchanges the username and password for a session key, which does not
+// expire unless the user revokes it.
+pub fn log_in(settings: &ScrobbleSettings, password: &str) -> Result<String, String> {
+    settings.check_server()?;
+    if settings.username.trim().is_empty() {
+        return Err("Enter the username to log in to the scrobbling server".to_string());
+    }
+    let params = BTreeMap::from([
+        ("username".to_string(), settings.username.trim().to_string()),
+        ("password".to_string(), password.to_string()),
+    ]);
+    let body = Client::new(settings.clone())
+        .call("auth.getMobileSession", params)
+        .map_err(|e| format!("Failed to log in: {}", e))?;
+    body.pointer("/session/key")
+        .and_then(Value::as_str)
+        .map(str::to_string)
+        .ok_or_else(|| "Failed to log in: auth.getMobileSession returned no session key".to_string())
+}
+
+// A signed Last.fm API client for one set of credentials.
+struct Client {
+    settings: ScrobbleSettings,
+}
+
+impl Client {
+    fn new(settings: ScrobbleSettings) -> Self {
+        Client { settings }
+    }
+
+    // Signs the call as the API asks: every parameter sorted by name,
+    // concatenated as name and value, followed by the shared secret, MD5'd.
+    fn signature(&self, params: &BTreeMap<String, String>) -> String {
+        let mut payload = String::new();
+        for (name, value) in params {
+            payload.push_str(name);
+            payload.push_str(value);
+        }
+        payload.push_str(&self.settings.api_secret);
+        format!("{:x}", md5::compute(payload.as_bytes()))
+    }
+
+    fn call(&self, method: &str, mut params: BTreeMap<String, String>) -> Result<Value, SubmitError> {
+        params.insert("method".to_string(), method.to_string());
+        params.insert("api_key".to_string(), self.settings.api_key.clone());
+        let signature = self.signature(&params);
+        params.insert("api_sig".to_string(), signature);
+        // Not part of the signature.
+        params.insert("format".to_string(), "json".to_string());
+
+        let form: Vec<(&str, &str)> = params.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        let url = &self.settings.api_url;
+        // Errors come back as JSON with a 4xx status, so read the body either way.
+        let response = match ureq::post(url).timeout(REQUEST_TIMEOUT).send_form(&form) {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Err(SubmitError::Retry(format!("Failed to reach {}: {}", url, e))),
+        };
+        let status = response.status();
+        let body: Value = response.into_json()
+            .map_err(|e| SubmitError::Retry(format!("Invalid response from {} (HTTP {}): {}", url, status, e)))?;
+
+        if let Some(code) = body.get("error").and_then(Value::as_u64) {
+            let message = format!(
+                "{} failed: {} (error {})",
+                method,
+                body.get("message").and_then(Value::as_str).unwrap_or("unknown error"),
+                code
+            );
+            return Err(match code {
+                // Invalid session key.
+                9 => SubmitError::Session(message),
+                // Service offline, temporarily unavailable, rate limit exceeded.
+                11 | 16 | 29 => SubmitError::Retry(message),
+                _ => SubmitError::Rejected(message),
+            });
+        }
+        if status >= 500 {
+            return Err(SubmitError::Retry(format!("{} failed: HTTP {}", method, status)));
+        }
+        Ok(body)
+    }
+
+    // A revoked session key keeps the queue waiting, like any other retry,
+    // until the user logs in again.
+    fn authenticated_call(&self, method: &str, mut params: BTreeMap<String, String>) -> Result<Value, SubmitError> {
+        params.insert("sk".to_string(), self.settings.session_key.clone());
+        self.call(method, params).map_err(|e| match e {
+            SubmitError::Session(e) => SubmitError::Session(format!("{}; log in again in the settings", e)),
+            e => e,
+        })
+    }
+
+    fn update_now_playing(&self, listen: &Listen) -> Result<(), SubmitError> {
+        let mut params = BTreeMap::from([
+            ("artist".to_string(), listen.artist.clone()),
+            ("track".to_string(), listen.title.clone()),
+        ]);
+        if let Some(album) = &listen.album {
+            params.insert("album".to_string(), album.clone());
+        }
+        if let Some(duration) = listen.duration {
+            params.insert("duration".to_string(), (duration.round() as u64).to_string());
+        }
+        self.authenticated_call("track.updateNowPlaying", params).map(|_| ())
+    }
+
+    fn scrobble(&self, listens: &[Listen]) -> Result<(), SubmitError> {
+        let mut params = BTreeMap::new();
+        for (i, listen) in listens.iter().enumerate() {
+            params.insert(format!("artist[{}]", i), listen.artist.clone());
+            params.insert(format!("track[{}]", i), listen.title.clone());
+            params.insert(format!("timestamp[{}]", i), listen.started_at.to_string());
+            if let Some(album) = &listen.album {
+                params.insert(format!("album[{}]", i), album.clone());
+            }
+            if let Some(duration) = listen.duration {
+                params.insert(format!("duration[{}]", i), (duration.round() as u64).to_string());
+            }
+        }
+        self.authenticated_call("track.scrobble", params).map(|_| ())
+    }
+}
+
+impl ListenService for Client {
+    fn now_playing(&mut self, listen: &Listen) -> Result<(), SubmitError> {
+        self.update_now_playing(listen)
+    }
+
+    fn submit(&mut self, listens: &[Listen]) -> Result<(), SubmitError> {
+        self.scrobble(listens)
+    }
+}
+
+pub fn start(app: tauri::AppHandle) {
+    listens::start(app, listens::Service {
+        name: "Last.fm scrobbler",
+        queue_file: QUEUE_FILE,
+        batch_size: BATCH_SIZE,
+        settings: |settings| Some(settings.scrobbling.clone())
+            .filter(|scrobbling| scrobbling.enabled && scrobbling.validate().is_ok()),
+        connect: |settings| Box::new(Client::new(settings)),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::listens::ListenQueue;
+    use crate::test_support::{TempDir, TestServer, listen};
+
+    fn settings(api_url: &str) -> ScrobbleSettings {
+        ScrobbleSettings {
+            enabled: true,
+            api_url: api_url.to_string(),
+            api_key: "key".to_string(),
+            api_secret: "secret".to_string(),
+            username: "user".to_string(),
+            session_key: "session".to_string(),
+        }
+    }
+
+    // Signs what the server received again, the way Last.fm checks it.
+    fn assert_signed(form: &BTreeMap<String, String>) {
+        let mut params = form.clone();
+        let signature = params.remove("api_sig").expect("request is not signed");
+        assert_eq!(params.remove("format").as_deref(), Some("json"));
+        assert_eq!(Client::new(settings("")).signature(&params), signature);
+    }
+
+    #[test]
+    fn signature_is_md5_of_sorted_params_and_secret() {
+        let params = BTreeMap::from([
+            ("username".to_string(), "user".to_string()),
+            ("method".to_string(), "auth.getMobileSession".to_string()),
+            ("password".to_string(), "pw".to_string()),
+            ("api_key".to_string(), "key".to_string()),
+        ]);
+        // md5("api_keykeymethodauth.getMobileSessionpasswordpwusernameusersecret")
+        assert_eq!(Client::new(settings("")).signature(&params), "b00662f7e0a4e2c593868c3739b595b6");
+    }
+
+    #[test]
+    fn log_in_exchanges_the_password_for_a_session_key() {
+        let server = TestServer::start(vec![(200, r#"{"session":{"name":"user","key":"new-key","subscriber":0}}"#)]);
+        assert_eq!(log_in(&settings(&server.url), "pass word").unwrap(), "new-key");
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].header("content-type"), Some("application/x-www-form-urlencoded"));
+        let form = requests[0].form();
+        assert_eq!(form["method"], "auth.getMobileSession");
+        assert_eq!(form["username"], "user");
+        assert_eq!(form["password"], "pass word");
+        assert!(!form.contains_key("sk"));
+        assert_signed(&form);
+    }
+
+    #[test]
+    fn log_in_without_a_session_key_fails() {
+        let server = TestServer::start(vec![(403, r#"{"error":4,"message":"Authentication Failed"}"#)]);
+        let error = log_in(&settings(&server.url), "wrong").unwrap_err();
+        assert!(error.contains("Authentication Failed"), "{}", error);
+    }
+
+    #[test]
+    fn scrobble_sends_a_batch_in_one_signed_request() {
+        let server = TestServer::start(vec![(200, r#"{"scrobbles":{"@attr":{"accepted":2,"ignored":0}}}"#)]);
+        let client = Client::new(settings(&server.url));
+        client.scrobble(&[listen("One", 1000), listen("Two", 1300)]).unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let form = requests[0].form();
+        assert_eq!(form["method"], "track.scrobble");
+        assert_eq!(form["sk"], "session");
+        assert_eq!(form["track[0]"], "One");
+        assert_eq!(form["track[1]"], "Two");
+        assert_eq!(form["artist[1]"], "Artist");
+        assert_eq!(form["album[0]"], "Album");
+        assert_eq!(form["timestamp[1]"], "1300");
+        assert_eq!(form["duration[0]"], "200");
+        assert_signed(&form);
+    }
+
+    #[test]
+    fn errors_are_classified() {
+        let server = TestServer::start(vec![
+            (403, r#"{"error":9,"message":"Invalid session key"}"#),
+            (200, r#"{"error":29,"message":"Rate limit exceeded"}"#),
+            (503, "Service Unavailable"),
+            (502, "{}"),
+            (400, r#"{"error":6,"message":"Invalid parameters"}"#),
+        ]);
+        let client = Client::new(settings(&server.url));
+        let batch = [listen("One", 1000)];
+
+        assert!(matches!(client.scrobble(&batch), Err(SubmitError::Session(_))));
+        assert!(matches!(client.scrobble(&batch), Err(SubmitError::Retry(_))));
+        assert!(matches!(client.scrobble(&batch), Err(SubmitError::Retry(_))));
+        assert!(matches!(client.scrobble(&batch), Err(SubmitError::Retry(_))));
+        assert!(matches!(client.scrobble(&batch), Err(SubmitError::Rejected(_))));
+    }
+
+    #[test]
+    fn unreachable_server_is_retried() {
+        // Nothing listens on port 9 of this machine.
+        let client = Client::new(settings("http://127.0.0.1:9/2.0/"));
+        assert!(matches!(client.scrobble(&[listen("One", 1000)]), Err(SubmitError::Retry(_))));
+    }
+
+    #[test]
+    fn queued_listens_are_replayed_in_batches_after_an_outage() {
+        let dir = TempDir::new("scrobble-queue");
+        let path = dir.path().join(QUEUE_FILE);
+        let mut queue = ListenQueue::open(Some(path.clone()));
+        for (i, title) in ["One", "Two", "Three"].iter().enumerate() {
+            queue.push(listen(title, 1000 + i as u64));
+        }
+
+        let server = TestServer::start(vec![(503, "{}")]);
+        let mut client = Client::new(settings(&server.url));
+        let result = listens::flush_queue(&mut queue, &mut client, 2, "test");
+        assert!(matches!(result, Err(SubmitError::Retry(_))));
+        assert_eq!(queue.len(), 3);
+
+        // After a restart the queue is read back from disk and sent oldest
+        // first.
+        let mut queue = ListenQueue::open(Some(path.clone()));
+        let server = TestServer::start(vec![(200, "{}"), (200, "{}")]);
+        let mut client = Client::new(settings(&server.url));
+        listens::flush_queue(&mut queue, &mut client, 2, "test").unwrap();
+        assert!(queue.is_empty());
+        assert!(ListenQueue::open(Some(path)).is_empty());
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 2);
+        let (first, second) = (requests[0].form(), requests[1].form());
+        assert_eq!((first["track[0]"].as_str(), first["track[1]"].as_str()), ("One", "Two"));
+        assert_eq!(second["track[0]"], "Three");
+        assert!(!second.contains_key("track[1]"));
+    }
+}
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+use tauri::Manager;
+
+const DEFAULT_PACK: &str = include_str!("../selectors/default.json");
+const PACK_FORMAT: &str = "qobuz-player-selectors";
+const PACK_VERSION: u32 = 1;
+pub const USER_PACK_FILE: &str = "selectors.json";
+
+// Player actions that are carried out by clicking an element. Seek and
+// volume talk to the media element directly and have no selectors.
+const ACTIONS: &[&str] = &[
+    "play",
+    "pause",
+    "toggle",
+    "next",
+    "previous",
+    "toggle_shuffle",
+    "cycle_repeat",
+    "toggle_favorite",
+];
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+static PACK: OnceLock<RwLock<SelectorPack>> = OnceLock::new();
+static PROBES: OnceLock<Mutex<HashMap<u64, mpsc::Sender<ProbeResults>>>> = OnceLock::new();
+static NEXT_PROBE: AtomicU64 = AtomicU64::new(1);
+
+// On-disk format shared by the bundled pack and the user's selectors.json.
+// A user pack only needs the actions it changes; each list replaces the
+// bundled list for that action.
+#[derive(Debug, Clone, Deserialize)]
+struct PackFile {
+    format: String,
+    version: u32,
+    #[serde(default)]
+    revision: Option<String>,
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SelectorSource {
+    Default,
+    User,
+}
+
+#[derive(Debug, Clone, Default)]
+struct SelectorPack {
+    revision: String,
+    user_revision: Option<String>,
+    actions: BTreeMap<String, (Vec<String>, SelectorSource)>,
+    warning: Option<String>,
+}
+
+// Per selector outcome of a live DOM probe, as sent back by PROBE_JS.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SelectorMatch {
+    pub selector: String,
+    pub matches: u32,
+    pub error: Option<String>,
+}
+
+type ProbeResults = BTreeMap<String, Vec<SelectorMatch>>;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActionReport {
+    pub action: String,
+    pub source: SelectorSource,
+    // The selector a command would click right now, if any.
+    pub active: Option<String>,
+    pub selectors: Vec<SelectorMatch>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelectorReport {
+    pub revision: String,
+    pub user_revision: Option<String>,
+    pub user_file: Option<String>,
+    pub warning: Option<String>,
+    pub actions: Vec<ActionReport>,
+}
+
+fn parse_pack(contents: &str) -> Result<PackFile, String> {
+    let pack: PackFile = serde_json::from_str(contents)
+        .map_err(|e| format!("Failed to parse selector pack: {}", e))?;
+
+    if pack.format != PACK_FORMAT {
+        return Err(format!("Not a selector pack (format \"{}\")", pack.format));
+    }
+    if pack.version == 0 || pack.version > PACK_VERSION {
+        return Err(format!(
+            "Selector pack version {} is not supported (expected {})",
+            pack.version, PACK_VERSION
+        ));
+    }
+    for (action, selectors) in &pack.actions {
+        if !ACTIONS.contains(&action.as_str()) {
+            return Err(format!("Unknown player action \"{}\"", action));
+        }
+        if selectors.is_empty() {
+            return Err(format!("No selectors given for \"{}\"", action));
+        }
+        if selectors.iter().any(|s| s.trim().is_empty()) {
+            return Err(format!("Empty selector for \"{}\"", action));
+        }
+    }
+    Ok(pack)
+}
+
+fn default_pack() -> Result<SelectorPack, String> {
+    let pack = parse_pack(DEFAULT_PACK).map_err(|e| format!("Bundled selector pack is invalid: {}", e))?;
+    let actions = pack.actions.into_iter()
+        .map(|(action, selectors)| (action, (selectors, SelectorSource::Default)))
+        .collect();
+    Ok(SelectorPack {
+        revision: pack.revision.unwrap_or_default(),
+        user_revision: None,
+        actions,
+        warning: None,
+    })
+}
+
+pub fn user_pack_path() -> Result<std::path::PathBuf, String> {
+    Ok(crate::paths::config_dir()?.join(USER_PACK_FILE))
+}
+
+fn load() -> Result<SelectorPack, String> {
+    load_from(&user_pack_path()?)
+}
+
+fn load_from(path: &std::path::Path) -> Result<SelectorPack, String> {
+    let mut pack = default_pack()?;
+    if !path.exists() {
+        return Ok(pack);
+    }
+
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let user = parse_pack(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    pack.user_revision = Some(user.revision.unwrap_or_default());
+    for (action, selectors) in user.actions {
+        pack.actions.insert(action, (selectors, SelectorSource::User));
+    }
+    Ok(pack)
+}
+
+// A user pack that cannot be loaded leaves the bundled selectors in use,
+// with the problem shown in the selector report.
+fn or_bundled(loaded: Result<SelectorPack, String>) -> SelectorPack {
+    loaded.unwrap_or_else(|e| {
+        eprintln!("Using the bundled player selectors: {}", e);
+        SelectorPack { warning: Some(e), ..default_pack().unwrap_or_default() }
+    })
+}
+
+fn pack() -> &'static RwLock<SelectorPack> {
+    PACK.get_or_init(|| RwLock::new(or_bundled(load())))
+}
+
+pub fn init() {
+    let _ = pack();
+}
+
+// Re-reads selectors.json. On error the selectors in use are kept and the
+// problem is reported through the selector report.
+pub fn reload() -> Result<(), String> {
+    let result = load();
+    let mut pack = pack().write()
+        .map_err(|e| format!("Failed to lock player selectors: {}", e))?;
+    match result {
+        Ok(loaded) => {
+            *pack = loaded;
+            Ok(())
+        }
+        Err(e) => {
+            pack.warning = Some(e.clone());
+            Err(e)
+        }
+    }
+}
+
+pub fn for_action(action: &str) -> Vec<String> {
+    pack().read()
+        .ok()
+        .and_then(|pack| pack.actions.get(action).map(|(selectors, _)| selectors.clone()))
+        .unwrap_or_default()
+}
+
+const PROBE_JS: &str = r#"
+(function(probe, actions) {
+    const results = {};
+    for (const [action, selectors] of Object.entries(actions)) {
+        results[action] = selectors.map(selector => {
+            try {
+                return { selector, matches: document.querySelectorAll(selector).length, error: null };
+            } catch (e) {
+                return { selector, matches: 0, error: String(e.message || e) };
+            }
+        });
+    }
+    window.__TAURI__.core.invoke('report_selector_matches', { probe, results });
+})"#;
+
+fn probes() -> &'static Mutex<HashMap<u64, mpsc::Sender<ProbeResults>>> {
+    PROBES.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn deliver_probe(probe: u64, results: ProbeResults) {
+    let sender = probes().lock().ok().and_then(|mut probes| probes.remove(&probe));
+    if let Some(sender) = sender {
+        let _ = sender.send(results);
+    }
+}
+
+// Runs every selector against the page currently loaded in the main window.
+// Blocks until the page reports back, so call it off the main thread.
+pub fn check(app: &tauri::AppHandle) -> Result<SelectorReport, String> {
+    let window = app.get_webview_window("main")
+        .ok_or_else(|| "Main window not found".to_string())?;
+    let (revision, user_revision, warning, actions) = {
+        let pack = pack().read()
+            .map_err(|e| format!("Failed to lock player selectors: {}", e))?;
+        (pack.revision.clone(), pack.user_revision.clone(), pack.warning.clone(), pack.actions.clone())
+    };
+
+    let selectors: BTreeMap<&String, &Vec<String>> = actions.iter()
+        .map(|(action, (selectors, _))| (action, selectors))
+        .collect();
+    let selectors = serde_json::to_string(&selectors)
+        .map_err(|e| format!("Failed to serialize player selectors: {}", e))?;
+
+    let probe = NEXT_PROBE.fetch_add(1, Ordering::Relaxed);
+    let (tx, rx) = mpsc::channel();
+    probes().lock()
+        .map_err(|e| format!("Failed to lock selector probes: {}", e))?
+        .insert(probe, tx);
+
+    let results = window.eval(&format!("{}({}, {});", PROBE_JS, probe, selectors))
+        .map_err(|e| format!("Failed to check player selectors: {}", e))
+        .and_then(|_| {
+            rx.recv_timeout(PROBE_TIMEOUT)
+                .map_err(|_| "The player page did not answer the selector check".to_string())
+        });
+    if let Ok(mut probes) = probes().lock() {
+        probes.remove(&probe);
+    }
+    let mut results = results?;
+
+    let actions = actions.into_iter()
+        .map(|(action, (_, source))| {
+            let selectors = results.remove(&action).unwrap_or_default();
+            let active = selectors.iter()
+                .find(|m| m.matches > 0 && m.error.is_none())
+                .map(|m| m.selector.clone());
+            ActionReport { action, source, active, selectors }
+        })
+        .collect();
+
+    let user_file = user_pack_path().ok()
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string());
+
+    Ok(SelectorReport { revision, user_revision, user_file, warning, actions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn pack_json(actions: &str) -> String {
+        format!(
+            r#"{{ "format": "qobuz-player-selectors", "version": 1, "revision": "test", "actions": {} }}"#,
+            actions
+        )
+    }
+
+    #[test]
+    fn bundled_pack_covers_every_action() {
+        let pack = default_pack().unwrap();
+        for action in ACTIONS {
+            assert!(pack.actions.contains_key(*action), "no selectors for {}", action);
+        }
+    }
+
+    #[test]
+    fn valid_pack_is_parsed() {
+        let pack = parse_pack(&pack_json(r#"{ "next": [".next", "button.skip"] }"#)).unwrap();
+        assert_eq!(pack.revision.as_deref(), Some("test"));
+        assert_eq!(pack.actions["next"], vec![".next", "button.skip"]);
+    }
+
+    #[test]
+    fn invalid_packs_are_rejected() {
+        let cases = [
+            (pack_json(r#"{ "rewind": [".rewind"] }"#), "Unknown player action \"rewind\""),
+            (pack_json(r#"{ "next": [] }"#), "No selectors given for \"next\""),
+            (pack_json(r#"{ "next": [" "] }"#), "Empty selector for \"next\""),
+            (
+                r#"{ "format": "something-else", "version": 1 }"#.to_string(),
+                "Not a selector pack (format \"something-else\")",
+            ),
+            (
+                r#"{ "format": "qobuz-player-selectors", "version": 2 }"#.to_string(),
+                "Selector pack version 2 is not supported (expected 1)",
+            ),
+        ];
+        for (contents, error) in cases {
+            assert_eq!(parse_pack(&contents).unwrap_err(), error);
+        }
+        assert!(parse_pack("{ not json").unwrap_err().starts_with("Failed to parse selector pack"));
+    }
+
+    #[test]
+    fn user_pack_replaces_only_its_actions() {
+        let dir = TempDir::new("selectors");
+        let path = dir.path().join(USER_PACK_FILE);
+        std::fs::write(&path, pack_json(r#"{ "next": [".my-next"] }"#)).unwrap();
+
+        let pack = load_from(&path).unwrap();
+        assert_eq!(pack.user_revision.as_deref(), Some("test"));
+        assert_eq!(pack.actions["next"], (vec![".my-next".to_string()], SelectorSource::User));
+        assert_eq!(pack.actions["play"].1, SelectorSource::Default);
+    }
+
+    #[test]
+    fn malformed_user_pack_falls_back_to_bundled() {
+        let dir = TempDir::new("selectors");
+        let path = dir.path().join(USER_PACK_FILE);
+        std::fs::write(&path, "{ \"format\": ").unwrap();
+
+        let pack = or_bundled(load_from(&path));
+        let warning = pack.warning.unwrap();
+        assert!(warning.contains("Failed to parse selector pack"), "{}", warning);
+        assert_eq!(pack.actions, default_pack().unwrap().actions);
+        assert!(pack.actions.values().all(|(_, source)| *source == SelectorSource::Default));
+    }
+}
@@ -1,13 +1,35 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+pub const SETTINGS_VERSION: u32 = 1;
+// Stands in for secrets in everything the settings overlay and exported
+// bundles see. Saving it back keeps the stored secret.
+pub const REDACTED: &str = "********";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
+    pub version: u32,
     pub close_to_tray: bool,
     pub minimize_to_tray: bool,
     pub launch_on_login: bool,
     pub launch_mode: LaunchMode,
+    pub keybindings: Keybindings,
+    pub keymap: crate::keymap::Keymap,
+    pub tray_actions: crate::tray::TrayActions,
+    pub tray_icon: crate::tray_icon::TrayIconSettings,
+    pub scrobbling: crate::scrobble::ScrobbleSettings,
+    pub listenbrainz: crate::listenbrainz::ListenBrainzSettings,
+    pub discord: crate::discord::DiscordSettings,
+    pub file_output: crate::file_output::FileOutput,
+    pub remote_api: crate::remote_api::RemoteApiSettings,
+    // Keys this build does not know about, kept so a newer or older build
+    // sharing the same settings.json does not lose them on save.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -19,114 +41,596 @@ pub enum LaunchMode {
     Maximized,
 }
 
+impl LaunchMode {
+    pub fn as_flag(&self) -> Option<&'static str> {
+        match self {
+            LaunchMode::Restored => None,
+            LaunchMode::Minimized => Some("--minimized"),
+            LaunchMode::MinimizedToTray => Some("--minimized-to-tray"),
+            LaunchMode::Maximized => Some("--maximized"),
+        }
+    }
+
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "--minimized" => Some(LaunchMode::Minimized),
+            "--minimized-to-tray" => Some(LaunchMode::MinimizedToTray),
+            "--maximized" => Some(LaunchMode::Maximized),
+            _ => None,
+        }
+    }
+}
+
+// Global shortcuts as accelerator strings such as "Ctrl+Alt+P". Unset
+// actions have no shortcut.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybindings {
+    pub play_pause: Option<String>,
+    pub next: Option<String>,
+    pub previous: Option<String>,
+    pub volume_up: Option<String>,
+    pub volume_down: Option<String>,
+    pub like: Option<String>,
+    pub toggle_window: Option<String>,
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Settings {
+            version: SETTINGS_VERSION,
             close_to_tray: true,
             minimize_to_tray: false,
             launch_on_login: false,
             launch_mode: LaunchMode::Restored,
+            keybindings: Keybindings::default(),
+            keymap: crate::keymap::Keymap::default(),
+            tray_actions: crate::tray::TrayActions::default(),
+            tray_icon: crate::tray_icon::TrayIconSettings::default(),
+            scrobbling: crate::scrobble::ScrobbleSettings::default(),
+            listenbrainz: crate::listenbrainz::ListenBrainzSettings::default(),
+            discord: crate::discord::DiscordSettings::default(),
+            file_output: crate::file_output::FileOutput::default(),
+            remote_api: crate::remote_api::RemoteApiSettings::default(),
+            extra: Map::new(),
         }
     }
 }
 
 impl Settings {
-    fn get_config_path() -> Result<PathBuf, String> {
-        let config_dir = dirs::config_dir()
-            .ok_or_else(|| "Could not determine config directory".to_string())?;
-        let app_config = config_dir.join("qobuz-player");
-        if !app_config.exists() {
-            fs::create_dir_all(&app_config)
-                .map_err(|e| format!("Failed to create config directory: {}", e))?;
-        }
-        Ok(app_config.join("settings.json"))
+    pub fn get_config_path() -> Result<PathBuf, String> {
+        Ok(crate::paths::config_dir()?.join("settings.json"))
     }
 
-    pub fn load() -> Self {
+    pub fn load() -> LoadResult {
         match Self::get_config_path() {
-            Ok(path) => {
-                if path.exists() {
-                    match fs::read_to_string(&path) {
-                        Ok(contents) => {
-                            serde_json::from_str::<Settings>(&contents).unwrap_or_default()
-                        }
-                        Err(_) => Settings::default(),
+            Ok(path) => Self::load_from(&path),
+            Err(e) => LoadResult::defaults(LoadStatus::Defaults { reason: e, corrupt_path: None }),
+        }
+    }
+
+    pub fn load_from(path: &Path) -> LoadResult {
+        if !path.exists() {
+            return LoadResult::defaults(LoadStatus::Missing);
+        }
+
+        let error = match fs::read_to_string(path) {
+            Ok(contents) => match Self::from_json_lenient(&contents) {
+                Ok((settings, file_keys, reset)) if reset.is_empty() => {
+                    return LoadResult { settings, file_keys, status: LoadStatus::Loaded };
+                }
+                Ok((settings, file_keys, reset)) => {
+                    // Keep a copy: the next save writes the defaults over
+                    // the sections that were reset.
+                    let corrupt_path = copy_aside(path).map(|p| p.display().to_string());
+                    if let Err(e) = &corrupt_path {
+                        eprintln!("Failed to keep a copy of the settings file: {}", e);
                     }
-                } else {
-                    Settings::default()
+                    return LoadResult {
+                        settings,
+                        file_keys,
+                        status: LoadStatus::PartlyLoaded { reset, corrupt_path: corrupt_path.ok() },
+                    };
                 }
+                Err(e) => e,
+            },
+            Err(e) => {
+                // Leave an unreadable file alone; it is not known to be corrupt.
+                return LoadResult::defaults(LoadStatus::Defaults {
+                    reason: format!("Failed to read settings file: {}", e),
+                    corrupt_path: None,
+                });
+            }
+        };
+
+        let corrupt_path = quarantine(path).map(|p| p.display().to_string());
+        if let Err(e) = &corrupt_path {
+            eprintln!("Failed to move corrupt settings file aside: {}", e);
+        }
+        let corrupt_path = corrupt_path.ok();
+
+        let backup_path = backup_path(path);
+        if let Ok(contents) = fs::read_to_string(&backup_path)
+            && let Ok((settings, file_keys)) = Self::from_json(&contents) {
+                return LoadResult {
+                    settings,
+                    file_keys,
+                    status: LoadStatus::RestoredFromBackup { reason: error, corrupt_path },
+                };
+            }
+
+        LoadResult::defaults(LoadStatus::Defaults { reason: error, corrupt_path })
+    }
+
+    // Also returns the settings keys the file actually contains, so callers
+    // can tell values read from disk apart from filled-in defaults.
+    pub fn from_json(contents: &str) -> Result<(Self, Vec<String>), String> {
+        let (settings, keys, reset) = Self::from_json_lenient(contents)?;
+        match reset.first() {
+            Some(problem) => Err(format!("Failed to read settings: {}", problem)),
+            None => Ok((settings, keys)),
+        }
+    }
+
+    // Like from_json, but a top-level section that does not fit, e.g. a
+    // string where a port number belongs, falls back to its default instead
+    // of failing the whole file. Also returns a description of each section
+    // that was reset.
+    pub fn from_json_lenient(contents: &str) -> Result<(Self, Vec<String>, Vec<String>), String> {
+        let value: Value = serde_json::from_str(contents)
+            .map_err(|e| format!("Failed to parse settings: {}", e))?;
+        let mut map = match migrate(value)? {
+            Value::Object(map) => map,
+            _ => return Err("Settings file is not a JSON object".to_string()),
+        };
+
+        let mut reset = Vec::new();
+        if let Err(e) = serde_json::from_value::<Settings>(Value::Object(map.clone())) {
+            let defaults = Self::default().to_map()?;
+            let bad: Vec<(String, String)> = map.iter()
+                .filter_map(|(key, value)| {
+                    let mut single = defaults.clone();
+                    single.insert(key.clone(), value.clone());
+                    serde_json::from_value::<Settings>(Value::Object(single))
+                        .err()
+                        .map(|e| (key.clone(), e.to_string()))
+                })
+                .collect();
+            if bad.is_empty() {
+                return Err(format!("Failed to read settings: {}", e));
+            }
+            for (key, error) in bad {
+                map.remove(&key);
+                reset.push(format!("\"{}\" ({})", key, error));
             }
-            Err(_) => Settings::default(),
         }
+
+        let keys = map.keys().filter(|k| *k != "version").cloned().collect();
+        let settings = serde_json::from_value(Value::Object(map))
+            .map_err(|e| format!("Failed to read settings: {}", e))?;
+        Ok((settings, keys, reset))
+    }
+
+    pub fn to_map(&self) -> Result<Map<String, Value>, String> {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(map)) => Ok(map),
+            Ok(_) => Err("Settings did not serialize to an object".to_string()),
+            Err(e) => Err(format!("Failed to serialize settings: {}", e)),
+        }
+    }
+
+    pub fn from_map(map: Map<String, Value>) -> Result<Self, String> {
+        serde_json::from_value(Value::Object(map))
+            .map_err(|e| format!("Invalid settings: {}", e))
+    }
+
+    fn secrets_mut(&mut self) -> [&mut String; 4] {
+        [
+            &mut self.scrobbling.api_secret,
+            &mut self.scrobbling.session_key,
+            &mut self.listenbrainz.token,
+            &mut self.remote_api.token,
+        ]
+    }
+
+    // A copy safe to hand to web content: every secret that is set is
+    // replaced with REDACTED.
+    pub fn redacted(&self) -> Settings {
+        let mut settings = self.clone();
+        for secret in settings.secrets_mut() {
+            if !secret.is_empty() {
+                *secret = REDACTED.to_string();
+            }
+        }
+        settings
+    }
+
+    // Puts back the secrets from `current` wherever these settings still
+    // carry REDACTED, i.e. the user left them alone.
+    pub fn restore_secrets(&mut self, current: &Settings) {
+        let mut current = current.clone();
+        for (secret, current) in self.secrets_mut().into_iter().zip(current.secrets_mut()) {
+            if secret == REDACTED {
+                *secret = std::mem::take(current);
+            }
+        }
+    }
+
+    // Checks everything serde cannot, such as shortcut syntax and service
+    // URLs. Every path that replaces the live settings goes through this.
+    pub fn validate(&self) -> Result<(), String> {
+        crate::shortcuts::parse(&self.keybindings)?;
+        self.keymap.validate()?;
+        self.tray_actions.validate()?;
+        self.scrobbling.validate()?;
+        self.listenbrainz.validate()?;
+        self.discord.validate()?;
+        self.file_output.validate()?;
+        self.remote_api.validate()
     }
 
     pub fn save(&self) -> Result<(), String> {
-        let path = Self::get_config_path()?;
+        self.save_to(&Self::get_config_path()?)
+    }
+
+    pub fn save_to(&self, path: &Path) -> Result<(), String> {
         let json = serde_json::to_string_pretty(self)
             .map_err(|e| format!("Failed to serialize settings: {}", e))?;
-        fs::write(&path, json)
+
+        if path.exists() {
+            fs::copy(path, backup_path(path))
+                .map_err(|e| format!("Failed to back up settings file: {}", e))?;
+        }
+
+        crate::atomic_file::write(path, json.as_bytes())
             .map_err(|e| format!("Failed to write settings file: {}", e))?;
         Ok(())
     }
 }
 
-#[cfg(target_os = "windows")]
-pub mod autostart {
-    use super::LaunchMode;
-    use winreg::enums::*;
-    use winreg::RegKey;
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LoadStatus {
+    Loaded,
+    Missing,
+    PartlyLoaded { reset: Vec<String>, corrupt_path: Option<String> },
+    RestoredFromBackup { reason: String, corrupt_path: Option<String> },
+    Defaults { reason: String, corrupt_path: Option<String> },
+}
 
-    const RUN_KEY_PATH: &str = "Software\\Microsoft\\Windows\\CurrentVersion\\Run";
-    const APP_NAME: &str = "QobuzPlayer";
+#[derive(Debug, Clone)]
+pub struct LoadResult {
+    pub settings: Settings,
+    pub file_keys: Vec<String>,
+    pub status: LoadStatus,
+}
 
-    pub fn enable(launch_mode: &LaunchMode) -> Result<(), String> {
-        let exe_path = std::env::current_exe()
-            .map_err(|e| format!("Failed to get executable path: {}", e))?;
-        
-        let mut command = format!("\"{}\"", exe_path.display());
-        
-        match launch_mode {
-            LaunchMode::Minimized => command.push_str(" --minimized"),
-            LaunchMode::MinimizedToTray => command.push_str(" --minimized-to-tray"),
-            LaunchMode::Maximized => command.push_str(" --maximized"),
-            LaunchMode::Restored => {}
+impl LoadResult {
+    fn defaults(status: LoadStatus) -> Self {
+        LoadResult { settings: Settings::default(), file_keys: Vec::new(), status }
+    }
+
+    pub fn warning(&self) -> Option<String> {
+        let kept = |corrupt_path: &Option<String>| match corrupt_path {
+            Some(p) => format!(" The broken file was kept as {}.", p),
+            None => String::new(),
+        };
+
+        match &self.status {
+            LoadStatus::Loaded | LoadStatus::Missing => None,
+            LoadStatus::PartlyLoaded { reset, corrupt_path } => Some(format!(
+                "Some settings in settings.json could not be read, so they use their defaults: {}.{}",
+                reset.join(", "),
+                match corrupt_path {
+                    Some(p) => format!(" A copy of the original file was kept as {}.", p),
+                    None => String::new(),
+                }
+            )),
+            LoadStatus::RestoredFromBackup { reason, corrupt_path } => Some(format!(
+                "settings.json could not be loaded ({}), so the last backup was restored.{}",
+                reason, kept(corrupt_path)
+            )),
+            LoadStatus::Defaults { reason, corrupt_path } => Some(format!(
+                "settings.json could not be loaded ({}), so default settings are in use.{}",
+                reason, kept(corrupt_path)
+            )),
         }
+    }
+}
 
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let run_key = hkcu
-            .open_subkey_with_flags(RUN_KEY_PATH, KEY_WRITE)
-            .map_err(|e| format!("Failed to open registry key: {}", e))?;
+fn backup_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(".bak");
+    path.with_file_name(name)
+}
 
-        run_key
-            .set_value(APP_NAME, &command)
-            .map_err(|e| format!("Failed to set registry value: {}", e))?;
+fn corrupt_path(path: &Path) -> PathBuf {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let mut name = path.file_name().map(|n| n.to_os_string()).unwrap_or_default();
+    name.push(format!(".corrupt-{}", timestamp));
+    path.with_file_name(name)
+}
 
-        Ok(())
+fn quarantine(path: &Path) -> Result<PathBuf, String> {
+    let target = corrupt_path(path);
+    fs::rename(path, &target)
+        .map_err(|e| format!("Failed to rename {}: {}", path.display(), e))?;
+    Ok(target)
+}
+
+fn copy_aside(path: &Path) -> Result<PathBuf, String> {
+    let target = corrupt_path(path);
+    fs::copy(path, &target)
+        .map_err(|e| format!("Failed to copy {}: {}", path.display(), e))?;
+    Ok(target)
+}
+
+type Migration = fn(&mut Map<String, Value>);
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1. Append new steps
+// here and bump SETTINGS_VERSION; never edit a step that has shipped.
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+pub fn migrate(value: Value) -> Result<Value, String> {
+    let mut map = match value {
+        Value::Object(map) => map,
+        _ => return Err("Settings file is not a JSON object".to_string()),
+    };
+
+    let file_version = map.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if file_version > SETTINGS_VERSION as u64 {
+        eprintln!(
+            "settings.json has version {} but this build only knows version {}; loading known fields only",
+            file_version, SETTINGS_VERSION
+        );
+        return Ok(Value::Object(map));
     }
 
-    pub fn disable() -> Result<(), String> {
-        let hkcu = RegKey::predef(HKEY_CURRENT_USER);
-        let run_key = hkcu
-            .open_subkey_with_flags(RUN_KEY_PATH, KEY_WRITE)
-            .map_err(|e| format!("Failed to open registry key: {}", e))?;
+    for (step, migration) in MIGRATIONS.iter().enumerate().skip(file_version as usize) {
+        migration(&mut map);
+        map.insert("version".to_string(), Value::from(step as u64 + 1));
+    }
 
-        run_key
-            .delete_value(APP_NAME)
-            .map_err(|e| format!("Failed to delete registry value: {}", e))?;
+    Ok(Value::Object(map))
+}
 
-        Ok(())
+// Version 0 files predate the version field. Older builds and hand-edited
+// files spell launch modes in several ways, so normalize them, and drop a
+// value we cannot map so only that field falls back to its default.
+fn migrate_v0_to_v1(map: &mut Map<String, Value>) {
+    if let Some(Value::String(mode)) = map.get("launch_mode") {
+        let normalized: String = mode
+            .chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_ascii_lowercase();
+        match normalized.as_str() {
+            "restored" | "normal" => {
+                map.insert("launch_mode".to_string(), Value::from("restored"));
+            }
+            "minimized" => {
+                map.insert("launch_mode".to_string(), Value::from("minimized"));
+            }
+            "minimizedtotray" | "tray" => {
+                map.insert("launch_mode".to_string(), Value::from("minimizedtotray"));
+            }
+            "maximized" => {
+                map.insert("launch_mode".to_string(), Value::from("maximized"));
+            }
+            _ => {
+                map.remove("launch_mode");
+            }
+        }
+    } else if map.contains_key("launch_mode") {
+        map.remove("launch_mode");
+    }
+
+    for key in ["close_to_tray", "minimize_to_tray", "launch_on_login"] {
+        if map.get(key).is_some_and(|v| !v.is_boolean()) {
+            map.remove(key);
+        }
     }
 }
 
-#[cfg(not(target_os = "windows"))]
-pub mod autostart {
-    use super::LaunchMode;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn fixture(name: &str) -> String {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/settings").join(name);
+        fs::read_to_string(&path).unwrap_or_else(|e| panic!("Failed to read {}: {}", path.display(), e))
+    }
+
+    fn load(name: &str) -> (Settings, Vec<String>) {
+        let (settings, mut keys) = Settings::from_json(&fixture(name)).unwrap();
+        keys.sort();
+        (settings, keys)
+    }
+
+    fn migrated(name: &str) -> Map<String, Value> {
+        match migrate(serde_json::from_str(&fixture(name)).unwrap()).unwrap() {
+            Value::Object(map) => map,
+            other => panic!("migrate returned {}", other),
+        }
+    }
+
+    #[test]
+    fn v0_normal_launch_mode_becomes_restored() {
+        let map = migrated("v0_launch_mode_normal.json");
+        assert_eq!(map["launch_mode"], "restored");
+        assert_eq!(map["version"], SETTINGS_VERSION);
+
+        let (settings, keys) = load("v0_launch_mode_normal.json");
+        assert_eq!(settings.launch_mode, LaunchMode::Restored);
+        assert!(!settings.close_to_tray);
+        assert_eq!(keys, ["close_to_tray", "launch_mode"]);
+    }
+
+    #[test]
+    fn v0_minimized_to_tray_spellings_are_normalized() {
+        for name in ["v0_launch_mode_minimized_to_tray.json", "v0_launch_mode_tray.json"] {
+            assert_eq!(migrated(name)["launch_mode"], "minimizedtotray", "{}", name);
+            let (settings, keys) = load(name);
+            assert_eq!(settings.launch_mode, LaunchMode::MinimizedToTray, "{}", name);
+            assert_eq!(keys, ["launch_mode"], "{}", name);
+        }
+    }
+
+    #[test]
+    fn v0_unknown_launch_mode_falls_back_to_default() {
+        let map = migrated("v0_launch_mode_unknown.json");
+        assert!(!map.contains_key("launch_mode"));
+
+        let (settings, keys) = load("v0_launch_mode_unknown.json");
+        assert_eq!(settings.launch_mode, LaunchMode::Restored);
+        assert!(settings.minimize_to_tray);
+        assert_eq!(keys, ["minimize_to_tray"]);
+    }
+
+    #[test]
+    fn v0_non_bool_flags_are_dropped() {
+        let map = migrated("v0_non_bool_flags.json");
+        for key in ["close_to_tray", "minimize_to_tray", "launch_on_login"] {
+            assert!(!map.contains_key(key), "{} was kept", key);
+        }
+
+        let (settings, keys) = load("v0_non_bool_flags.json");
+        let defaults = Settings::default();
+        assert_eq!(settings.close_to_tray, defaults.close_to_tray);
+        assert_eq!(settings.minimize_to_tray, defaults.minimize_to_tray);
+        assert_eq!(settings.launch_on_login, defaults.launch_on_login);
+        assert_eq!(settings.launch_mode, LaunchMode::Maximized);
+        assert_eq!(keys, ["launch_mode"]);
+    }
+
+    #[test]
+    fn unknown_keys_survive_in_extra() {
+        let (settings, keys) = load("v1_unknown_keys.json");
+        assert!(!settings.close_to_tray);
+        assert_eq!(settings.extra["theme"], "dark");
+        assert_eq!(settings.extra["future_feature"]["enabled"], true);
+        assert_eq!(keys, ["close_to_tray", "future_feature", "theme"]);
+
+        let saved = settings.to_map().unwrap();
+        assert_eq!(saved["theme"], "dark");
+        assert_eq!(saved["future_feature"]["enabled"], true);
+    }
 
-    pub fn enable(_launch_mode: &LaunchMode) -> Result<(), String> {
-        Err("Autostart is only supported on Windows".to_string())
+    #[test]
+    fn newer_version_is_loaded_without_migrating() {
+        let map = migrated("future_version.json");
+        assert_eq!(map["version"], 99);
+
+        let (settings, keys) = load("future_version.json");
+        assert_eq!(settings.version, 99);
+        assert_eq!(settings.launch_mode, LaunchMode::Minimized);
+        assert_eq!(settings.extra["new_setting"], serde_json::json!([1, 2]));
+        assert_eq!(keys, ["close_to_tray", "launch_mode", "new_setting"]);
+    }
+
+    #[test]
+    fn non_object_root_is_rejected() {
+        let value = serde_json::from_str(&fixture("not_an_object.json")).unwrap();
+        assert!(migrate(value).is_err());
+        assert!(Settings::from_json(&fixture("not_an_object.json")).is_err());
     }
 
-    pub fn disable() -> Result<(), String> {
-        Err("Autostart is only supported on Windows".to_string())
+    #[test]
+    fn bad_section_falls_back_on_its_own() {
+        let (settings, mut keys, reset) = Settings::from_json_lenient(&fixture("v1_bad_section.json")).unwrap();
+        keys.sort();
+        assert!(!settings.close_to_tray);
+        assert_eq!(settings.launch_mode, LaunchMode::Maximized);
+        assert_eq!(settings.remote_api, crate::remote_api::RemoteApiSettings::default());
+        assert_eq!(keys, ["close_to_tray", "launch_mode"]);
+        assert_eq!(reset.len(), 1);
+        assert!(reset[0].starts_with("\"remote_api\""), "{}", reset[0]);
+
+        // Strict parsing, as used for imports and reloads, still refuses it.
+        assert!(Settings::from_json(&fixture("v1_bad_section.json")).is_err());
+    }
+
+    fn files_in(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir).unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn save_backs_up_the_previous_file() {
+        let dir = TempDir::new("settings");
+        let path = dir.path().join("settings.json");
+        let mut settings = Settings::default();
+        settings.save_to(&path).unwrap();
+        settings.close_to_tray = false;
+        settings.save_to(&path).unwrap();
+
+        // No temp files are left behind.
+        assert_eq!(files_in(dir.path()), ["settings.json", "settings.json.bak"]);
+        let (saved, _) = Settings::from_json(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!saved.close_to_tray);
+        let (backup, _) = Settings::from_json(&fs::read_to_string(backup_path(&path)).unwrap()).unwrap();
+        assert!(backup.close_to_tray);
+
+        let result = Settings::load_from(&path);
+        assert!(matches!(result.status, LoadStatus::Loaded));
+        assert_eq!(result.settings, saved);
+    }
+
+    #[test]
+    fn corrupt_file_is_quarantined() {
+        let dir = TempDir::new("settings");
+        let path = dir.path().join("settings.json");
+        fs::write(&path, "{ \"close_to_tray\": fal").unwrap();
+
+        let result = Settings::load_from(&path);
+        let LoadStatus::Defaults { corrupt_path: Some(corrupt_path), .. } = &result.status else {
+            panic!("unexpected status {:?}", result.status);
+        };
+        assert_eq!(result.settings, Settings::default());
+        assert!(!path.exists());
+        assert!(corrupt_path.contains("settings.json.corrupt-"), "{}", corrupt_path);
+        assert_eq!(fs::read_to_string(corrupt_path).unwrap(), "{ \"close_to_tray\": fal");
+        assert!(result.warning().unwrap().contains(corrupt_path.as_str()));
+    }
+
+    #[test]
+    fn corrupt_file_is_restored_from_backup() {
+        let dir = TempDir::new("settings");
+        let path = dir.path().join("settings.json");
+        let mut settings = Settings::default();
+        settings.launch_mode = LaunchMode::Minimized;
+        settings.save_to(&path).unwrap();
+        settings.save_to(&path).unwrap();
+        fs::write(&path, "not json").unwrap();
+
+        let result = Settings::load_from(&path);
+        assert!(matches!(result.status, LoadStatus::RestoredFromBackup { .. }), "{:?}", result.status);
+        assert_eq!(result.settings.launch_mode, LaunchMode::Minimized);
+        assert!(files_in(dir.path()).iter().any(|name| name.starts_with("settings.json.corrupt-")));
+    }
+
+    #[test]
+    fn bad_section_keeps_the_rest_of_the_file() {
+        let dir = TempDir::new("settings");
+        let path = dir.path().join("settings.json");
+        fs::write(&path, fixture("v1_bad_section.json")).unwrap();
+
+        let result = Settings::load_from(&path);
+        let LoadStatus::PartlyLoaded { reset, corrupt_path: Some(corrupt_path) } = &result.status else {
+            panic!("unexpected status {:?}", result.status);
+        };
+        assert_eq!(reset.len(), 1);
+        assert_eq!(result.settings.launch_mode, LaunchMode::Maximized);
+        // The file stays in place and a copy keeps the values being reset.
+        assert!(path.exists());
+        assert_eq!(fs::read_to_string(corrupt_path).unwrap(), fixture("v1_bad_section.json"));
+        assert!(result.warning().unwrap().contains("remote_api"));
     }
 }
@@ -0,0 +1,129 @@
+use crate::settings::Settings;
+use notify::{RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::time::Duration;
+use tauri::Manager;
+
+// Editors and our own atomic save produce several events per change
+// (temp file, rename, metadata), so wait for the burst to settle.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+pub fn start(app: tauri::AppHandle) {
+    let path = match Settings::get_config_path() {
+        Ok(path) => path,
+        Err(e) => {
+            eprintln!("Settings watcher disabled: {}", e);
+            return;
+        }
+    };
+    let Some(dir) = path.parent().map(Path::to_path_buf) else {
+        return;
+    };
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(tx) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("Settings watcher disabled: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+        eprintln!("Settings watcher disabled: failed to watch {}: {}", dir.display(), e);
+        return;
+    }
+
+    let selectors_name = std::ffi::OsString::from(crate::selectors::USER_PACK_FILE);
+
+    std::thread::spawn(move || {
+        // Moved in so the watcher lives as long as this thread.
+        let _watcher = watcher;
+        let touches = |event: &notify::Result<notify::Event>, name: Option<&std::ffi::OsStr>| match event {
+            Ok(event) => event.paths.iter().any(|p| p.file_name() == name),
+            Err(_) => false,
+        };
+
+        while let Ok(event) = rx.recv() {
+            let mut settings_changed = touches(&event, path.file_name());
+            let mut selectors_changed = touches(&event, Some(&selectors_name));
+            if !settings_changed && !selectors_changed {
+                continue;
+            }
+            while let Ok(event) = rx.recv_timeout(DEBOUNCE) {
+                settings_changed |= touches(&event, path.file_name());
+                selectors_changed |= touches(&event, Some(&selectors_name));
+            }
+
+            if settings_changed {
+                reload(&app, &path);
+            }
+            if selectors_changed && let Err(e) = crate::selectors::reload() {
+                eprintln!("Ignoring selectors.json change, keeping previous selectors: {}", e);
+            }
+        }
+    });
+}
+
+fn reload(app: &tauri::AppHandle, path: &Path) {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(e) => {
+            eprintln!("Ignoring settings.json change: failed to read file: {}", e);
+            return;
+        }
+    };
+
+    let (file_settings, file_keys) = match Settings::from_json(&contents) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            report(app, e);
+            return;
+        }
+    };
+
+    let state = app.state::<crate::AppState>();
+    let settings = {
+        let Ok(mut config) = state.config.lock() else {
+            return;
+        };
+        let previous = config.clone();
+        config.set_file(file_settings, file_keys);
+        match config.resolve().and_then(|resolved| resolved.settings.validate().map(|_| resolved.settings)) {
+            Ok(settings) => settings,
+            Err(e) => {
+                *config = previous;
+                drop(config);
+                report(app, e);
+                return;
+            }
+        }
+    };
+
+    let unchanged = state
+        .settings
+        .lock()
+        .map(|current| *current == settings)
+        .unwrap_or(false);
+    if unchanged {
+        return;
+    }
+
+    if let Ok(mut warning) = state.settings_warning.lock() {
+        *warning = None;
+    }
+
+    if let Err(e) = crate::apply_settings(app, settings) {
+        eprintln!("Reloaded settings.json but failed to apply it: {}", e);
+    }
+}
+
+// Shown in the settings overlay as well as logged, since whoever edited the
+// file is unlikely to be watching the console.
+fn report(app: &tauri::AppHandle, error: String) {
+    let message = format!("Ignoring settings.json change, keeping previous settings: {}", error);
+    eprintln!("{}", message);
+    if let Ok(mut warning) = app.state::<crate::AppState>().settings_warning.lock() {
+        *warning = Some(message);
+    }
+}
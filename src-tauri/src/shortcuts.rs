@@ -0,0 +1,277 @@
+use crate::player::PlayerCommand;
+use crate::settings::Keybindings;
+use std::str::FromStr;
+use tauri::Manager;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+
+const VOLUME_STEP: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShortcutAction {
+    PlayPause,
+    Next,
+    Previous,
+    VolumeUp,
+    VolumeDown,
+    Like,
+    ToggleWindow,
+}
+
+impl ShortcutAction {
+    // Matches the Keybindings field names, which also serve as portal
+    // shortcut ids.
+    pub fn id(&self) -> &'static str {
+        match self {
+            ShortcutAction::PlayPause => "play_pause",
+            ShortcutAction::Next => "next",
+            ShortcutAction::Previous => "previous",
+            ShortcutAction::VolumeUp => "volume_up",
+            ShortcutAction::VolumeDown => "volume_down",
+            ShortcutAction::Like => "like",
+            ShortcutAction::ToggleWindow => "toggle_window",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ShortcutAction::PlayPause => "Play or pause",
+            ShortcutAction::Next => "Next track",
+            ShortcutAction::Previous => "Previous track",
+            ShortcutAction::VolumeUp => "Volume up",
+            ShortcutAction::VolumeDown => "Volume down",
+            ShortcutAction::Like => "Add or remove the current track from favorites",
+            ShortcutAction::ToggleWindow => "Show or hide the window",
+        }
+    }
+}
+
+fn bindings(keybindings: &Keybindings) -> [(ShortcutAction, &Option<String>); 7] {
+    [
+        (ShortcutAction::PlayPause, &keybindings.play_pause),
+        (ShortcutAction::Next, &keybindings.next),
+        (ShortcutAction::Previous, &keybindings.previous),
+        (ShortcutAction::VolumeUp, &keybindings.volume_up),
+        (ShortcutAction::VolumeDown, &keybindings.volume_down),
+        (ShortcutAction::Like, &keybindings.like),
+        (ShortcutAction::ToggleWindow, &keybindings.toggle_window),
+    ]
+}
+
+// Parses every binding and rejects two actions sharing a shortcut.
+pub fn parse(keybindings: &Keybindings) -> Result<Vec<(ShortcutAction, Shortcut)>, String> {
+    let mut parsed: Vec<(ShortcutAction, Shortcut)> = Vec::new();
+    for (action, accelerator) in bindings(keybindings) {
+        let Some(accelerator) = accelerator.as_deref().map(str::trim).filter(|a| !a.is_empty()) else {
+            continue;
+        };
+        let shortcut = Shortcut::from_str(accelerator)
+            .map_err(|e| format!("Invalid shortcut \"{}\" for {}: {}", accelerator, action.id(), e))?;
+        if let Some((other, _)) = parsed.iter().find(|(_, s)| *s == shortcut) {
+            return Err(format!(
+                "\"{}\" is assigned to both {} and {}",
+                accelerator, other.id(), action.id()
+            ));
+        }
+        parsed.push((action, shortcut));
+    }
+    Ok(parsed)
+}
+
+pub fn trigger(app: &tauri::AppHandle, action: ShortcutAction) -> Result<(), String> {
+    let command = match action {
+        ShortcutAction::PlayPause => PlayerCommand::Toggle,
+        ShortcutAction::Next => PlayerCommand::Next,
+        ShortcutAction::Previous => PlayerCommand::Previous,
+        ShortcutAction::Like => PlayerCommand::ToggleFavorite,
+        ShortcutAction::VolumeUp | ShortcutAction::VolumeDown => {
+            let state = app.state::<crate::AppState>();
+            let current = state.now_playing.lock().ok()
+                .and_then(|np| np.volume)
+                .unwrap_or(1.0);
+            let step = if action == ShortcutAction::VolumeUp { VOLUME_STEP } else { -VOLUME_STEP };
+            PlayerCommand::SetVolume { volume: (current + step).clamp(0.0, 1.0) }
+        }
+        ShortcutAction::ToggleWindow => {
+            let window = app.get_webview_window("main")
+                .ok_or_else(|| "Main window not found".to_string())?;
+            let visible = window.is_visible().unwrap_or(false) && !window.is_minimized().unwrap_or(false);
+            if visible {
+                return window.hide().map_err(|e| format!("Failed to hide window: {}", e));
+            }
+            crate::window_manager::show_main_window(app);
+            return Ok(());
+        }
+    };
+    crate::player::dispatch(app, &command)
+}
+
+// Replaces every registered shortcut with the given bindings. Called at
+// startup and whenever settings are applied.
+pub fn apply(app: &tauri::AppHandle, keybindings: &Keybindings) -> Result<(), String> {
+    let parsed = parse(keybindings)?;
+
+    #[cfg(target_os = "linux")]
+    {
+        if portal::available() {
+            return portal::bind(app, parsed);
+        }
+    }
+
+    register(app, parsed)
+}
+
+fn register(app: &tauri::AppHandle, parsed: Vec<(ShortcutAction, Shortcut)>) -> Result<(), String> {
+    let global_shortcut = app.global_shortcut();
+    global_shortcut.unregister_all()
+        .map_err(|e| format!("Failed to unregister shortcuts: {}", e))?;
+
+    // Keep going after a failure so one shortcut taken by another
+    // application does not disable the rest.
+    let mut failed = Vec::new();
+    for (action, shortcut) in parsed {
+        let result = global_shortcut.on_shortcut(shortcut, move |app, _shortcut, event| {
+            if event.state == ShortcutState::Pressed
+                && let Err(e) = trigger(app, action) {
+                    eprintln!("Failed to run shortcut \"{}\": {}", action.description(), e);
+                }
+        });
+        if let Err(e) = result {
+            failed.push(format!("{} ({})", shortcut, e));
+        }
+    }
+
+    if failed.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Could not register shortcuts, they may be in use by another application: {}", failed.join(", ")))
+    }
+}
+
+// Wayland compositors do not let applications grab keys, so shortcuts go
+// through the XDG GlobalShortcuts portal instead. The compositor shows its
+// own dialog to confirm the bindings and may let the user change them.
+#[cfg(target_os = "linux")]
+mod portal {
+    use super::ShortcutAction;
+    use ashpd::desktop::global_shortcuts::{GlobalShortcuts, NewShortcut};
+    use futures::channel::oneshot;
+    use futures::future::{self, Either};
+    use futures::StreamExt;
+    use std::sync::{Mutex, OnceLock};
+    use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
+
+    // Stops the task that owns the current portal session.
+    static STOP: Mutex<Option<oneshot::Sender<()>>> = Mutex::new(None);
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+
+    pub fn available() -> bool {
+        *AVAILABLE.get_or_init(|| {
+            std::env::var_os("WAYLAND_DISPLAY").is_some()
+                && tauri::async_runtime::block_on(GlobalShortcuts::new()).is_ok()
+        })
+    }
+
+    // Portal triggers follow the XDG shortcuts spec: modifiers in capitals
+    // and an XKB key name, e.g. "CTRL+ALT+p".
+    fn trigger(shortcut: &Shortcut) -> String {
+        let mut parts = Vec::new();
+        for (modifier, name) in [
+            (Modifiers::CONTROL, "CTRL"),
+            (Modifiers::ALT, "ALT"),
+            (Modifiers::SHIFT, "SHIFT"),
+            (Modifiers::SUPER, "LOGO"),
+        ] {
+            if shortcut.mods.contains(modifier) {
+                parts.push(name.to_string());
+            }
+        }
+
+        let code = shortcut.key.to_string();
+        let key = match shortcut.key {
+            Code::Space => "space".to_string(),
+            Code::ArrowLeft => "Left".to_string(),
+            Code::ArrowRight => "Right".to_string(),
+            Code::ArrowUp => "Up".to_string(),
+            Code::ArrowDown => "Down".to_string(),
+            Code::MediaPlayPause => "XF86AudioPlay".to_string(),
+            Code::MediaTrackNext => "XF86AudioNext".to_string(),
+            Code::MediaTrackPrevious => "XF86AudioPrev".to_string(),
+            Code::AudioVolumeUp => "XF86AudioRaiseVolume".to_string(),
+            Code::AudioVolumeDown => "XF86AudioLowerVolume".to_string(),
+            _ => code.strip_prefix("Key")
+                .or_else(|| code.strip_prefix("Digit"))
+                .map(str::to_ascii_lowercase)
+                .unwrap_or(code.clone()),
+        };
+        parts.push(key);
+        parts.join("+")
+    }
+
+    pub fn bind(app: &tauri::AppHandle, parsed: Vec<(ShortcutAction, Shortcut)>) -> Result<(), String> {
+        let (stop_tx, stop_rx) = oneshot::channel();
+        {
+            let mut stop = STOP.lock()
+                .map_err(|e| format!("Failed to lock shortcut portal: {}", e))?;
+            if let Some(previous) = stop.replace(stop_tx) {
+                let _ = previous.send(());
+            }
+        }
+        if parsed.is_empty() {
+            return Ok(());
+        }
+
+        let shortcuts: Vec<NewShortcut> = parsed.iter()
+            .map(|(action, shortcut)| {
+                NewShortcut::new(action.id(), action.description())
+                    .preferred_trigger(Some(trigger(shortcut).as_str()))
+            })
+            .collect();
+
+        let actions: Vec<ShortcutAction> = parsed.iter().map(|(action, _)| *action).collect();
+        let app = app.clone();
+        let (ready_tx, ready_rx) = std::sync::mpsc::channel();
+        tauri::async_runtime::spawn(async move {
+            let result = async {
+                let portal = GlobalShortcuts::new().await?;
+                let session = portal.create_session().await?;
+                portal.bind_shortcuts(&session, &shortcuts, None).await?.response()?;
+                let activated = portal.receive_activated().await?;
+                Ok::<_, ashpd::Error>((portal, session, activated))
+            }
+            .await;
+
+            let (_portal, session, mut activated) = match result {
+                Ok(bound) => {
+                    let _ = ready_tx.send(Ok(()));
+                    bound
+                }
+                Err(e) => {
+                    let _ = ready_tx.send(Err(format!("Failed to bind shortcuts through the desktop portal: {}", e)));
+                    return;
+                }
+            };
+
+            let mut stop_rx = stop_rx;
+            loop {
+                match future::select(activated.next(), stop_rx).await {
+                    Either::Left((Some(event), stop)) => {
+                        stop_rx = stop;
+                        if let Some(action) = actions.iter().find(|a| a.id() == event.shortcut_id())
+                            && let Err(e) = super::trigger(&app, *action) {
+                                eprintln!("Failed to run shortcut \"{}\": {}", action.description(), e);
+                            }
+                    }
+                    _ => break,
+                }
+            }
+            let _ = session.close().await;
+        });
+
+        // The portal may wait for the user to confirm the bindings, so do
+        // not hold up settings changes on it.
+        match ready_rx.recv_timeout(std::time::Duration::from_secs(2)) {
+            Ok(result) => result,
+            Err(_) => Ok(()),
+        }
+    }
+}
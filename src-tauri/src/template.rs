@@ -0,0 +1,181 @@
+use crate::now_playing::{NowPlaying, PlaybackState};
+
+// Placeholders a template can use, e.g. "{artist} - {title}".
+const FIELDS: &[&str] = &[
+    "title", "artist", "album", "cover_url", "duration", "position", "remaining", "state", "quality", "volume",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Text(String),
+    Field(&'static str),
+}
+
+// A user template: plain text with {field} placeholders, and {{ and }} for
+// literal braces. Missing values render as empty text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut chars = source.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if chars.peek().map(|(_, c)| *c) == Some('{') => {
+                    chars.next();
+                    text.push('{');
+                }
+                '}' if chars.peek().map(|(_, c)| *c) == Some('}') => {
+                    chars.next();
+                    text.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some((_, '}')) => break,
+                            Some((_, c)) => name.push(c),
+                            None => return Err(format!("Unclosed \"{{\" at position {} in \"{}\"", i + 1, source)),
+                        }
+                    }
+                    let name = name.trim();
+                    let field = FIELDS.iter().find(|f| **f == name).ok_or_else(|| {
+                        format!("Unknown field {{{}}} in \"{}\"; available: {}", name, source, FIELDS.join(", "))
+                    })?;
+                    if !text.is_empty() {
+                        parts.push(Part::Text(std::mem::take(&mut text)));
+                    }
+                    parts.push(Part::Field(field));
+                }
+                '}' => return Err(format!("Unmatched \"}}\" at position {} in \"{}\"; write }}}} for a literal brace", i + 1, source)),
+                c => text.push(c),
+            }
+        }
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Ok(Template { parts })
+    }
+
+    // `position` is passed in so callers can use the extrapolated position
+    // rather than the one from the last report.
+    pub fn render(&self, np: &NowPlaying, position: Option<f64>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => out.push_str(text),
+                Part::Field(field) => out.push_str(&field_value(field, np, position)),
+            }
+        }
+        out
+    }
+}
+
+fn field_value(field: &str, np: &NowPlaying, position: Option<f64>) -> String {
+    let text = |value: &Option<String>| value.clone().unwrap_or_default();
+    match field {
+        "title" => text(&np.title),
+        "artist" => text(&np.artist),
+        "album" => text(&np.album),
+        "cover_url" => text(&np.cover_url),
+        "quality" => text(&np.quality),
+        "duration" => np.duration.map(format_time).unwrap_or_default(),
+        "position" => position.map(format_time).unwrap_or_default(),
+        "remaining" => match (np.duration, position) {
+            (Some(duration), Some(position)) => format_time((duration - position).max(0.0)),
+            _ => String::new(),
+        },
+        "state" => match np.state {
+            PlaybackState::Playing => "playing",
+            PlaybackState::Paused => "paused",
+            PlaybackState::Stopped => "stopped",
+        }
+        .to_string(),
+        "volume" => np.volume.map(|v| format!("{}", (v * 100.0).round() as u32)).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+// 3:07, or 1:02:03 from an hour on.
+pub fn format_time(seconds: f64) -> String {
+    let total = seconds.max(0.0).floor() as u64;
+    let (hours, minutes, secs) = (total / 3600, (total / 60) % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playing() -> NowPlaying {
+        NowPlaying {
+            title: Some("So What".to_string()),
+            artist: Some("Miles Davis".to_string()),
+            album: Some("Kind of Blue".to_string()),
+            cover_url: Some("https://static.qobuz.com/cover.jpg".to_string()),
+            duration: Some(562.0),
+            position: Some(61.9),
+            state: PlaybackState::Playing,
+            quality: Some("24-Bit / 96 kHz".to_string()),
+            volume: Some(0.804),
+        }
+    }
+
+    fn render(source: &str, np: &NowPlaying) -> String {
+        Template::parse(source).unwrap().render(np, np.position)
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        let np = playing();
+        assert_eq!(render("{artist} - {title}", &np), "Miles Davis - So What");
+        assert_eq!(render("{album} ({quality})", &np), "Kind of Blue (24-Bit / 96 kHz)");
+        assert_eq!(render("{position} / {duration}, {remaining} left", &np), "1:01 / 9:22, 8:20 left");
+        assert_eq!(render("{state} at {volume}%", &np), "playing at 80%");
+        assert_eq!(render("{cover_url}", &np), "https://static.qobuz.com/cover.jpg");
+        assert_eq!(render("{ title }", &np), "So What");
+    }
+
+    #[test]
+    fn missing_fields_render_empty() {
+        let np = NowPlaying { title: Some("Track".to_string()), ..NowPlaying::default() };
+        assert_eq!(render("{title} by {artist} on {album}", &np), "Track by  on ");
+        assert_eq!(render("[{position}/{duration}|{remaining}|{volume}]", &np), "[/||]");
+        assert_eq!(render("{state}", &np), "stopped");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        let np = playing();
+        assert_eq!(render("{{title}}", &np), "{title}");
+        assert_eq!(render("{{{title}}}", &np), "{So What}");
+        assert_eq!(render("}}{{", &np), "}{");
+        assert_eq!(render("", &np), "");
+    }
+
+    #[test]
+    fn bad_templates_are_rejected() {
+        let unknown = Template::parse("{artist} - {song}").unwrap_err();
+        assert!(unknown.contains("Unknown field {song}"), "{}", unknown);
+        assert!(Template::parse("{title").unwrap_err().contains("Unclosed \"{\" at position 1"));
+        assert!(Template::parse("title}").unwrap_err().contains("Unmatched \"}\" at position 6"));
+    }
+
+    #[test]
+    fn time_switches_to_hours_from_an_hour_on() {
+        assert_eq!(format_time(0.0), "0:00");
+        assert_eq!(format_time(59.99), "0:59");
+        assert_eq!(format_time(187.0), "3:07");
+        assert_eq!(format_time(3723.0), "1:02:03");
+        assert_eq!(format_time(-5.0), "0:00");
+    }
+}
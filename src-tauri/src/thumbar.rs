@@ -274,18 +274,14 @@ mod windows_impl {
                 let notif = ((raw >> 16) & 0xffff) as u32;
                 const THBN_CLICKED: u32 = 0x1800;
                 if (100..=102).contains(&id) && notif == THBN_CLICKED
-                    && let Some(app) = APP_HANDLE.get()
-                        && let Some(window) = app.get_webview_window("main") {
-                            let js = match id {
-                                100 => "(function(){ let selectors = ['button[aria-label*=\"revious\"]', 'button[aria-label*=\"Previous\"]', 'button[aria-label*=\"PREVIOUS\"]', 'button[title*=\"revious\"]', 'button[title*=\"Previous\"]', '.pct-player-previous', '.player__action-previous', 'button[class*=\"previous\"]', 'button[class*=\"prev\"]', 'button[class*=\"back\"]', '[data-testid*=\"previous\"]', '[data-testid*=\"prev\"]', 'button.pct-player-previous', 'span.pct-player-previous']; for(let s of selectors) { let el = document.querySelector(s); if(el) { el.click(); return; } } })()".to_string(),
-                                101 => "(function(){ let m = document.querySelector('audio, video'); if(m) { if(m.paused) m.play(); else m.pause(); } else { document.querySelector('button[aria-label*=\"lay\"], button[aria-label*=\"ause\"], .play-button, .pause-button, .pct-player-play, .pct-player-pause')?.click(); } })()".to_string(),
-                                102 => "(function(){ let selectors = ['button[aria-label*=\"ext\"]', 'button[aria-label*=\"Next\"]', '.pct-player-next', 'button[class*=\"next\"]', '[data-testid*=\"next\"]']; for(let s of selectors) { let el = document.querySelector(s); if(el) { el.click(); return; } } })()".to_string(),
-                                _ => String::new()
-                            };
-                            if !js.is_empty() {
-                                let _ = window.eval(&js);
-                            }
-                        }
+                    && let Some(app) = APP_HANDLE.get() {
+                        let command = match id {
+                            100 => crate::player::PlayerCommand::Previous,
+                            101 => crate::player::PlayerCommand::Toggle,
+                            _ => crate::player::PlayerCommand::Next,
+                        };
+                        let _ = crate::player::dispatch(app, &command);
+                    }
             }
 
             let prev = PREV_WNDPROC.get().copied().unwrap_or(0);
@@ -0,0 +1,376 @@
+use crate::now_playing::{NowPlaying, PlaybackState};
+use crate::player::PlayerCommand;
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem};
+use tauri::tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent};
+use tauri::{Listener, Manager, WindowEvent};
+
+pub const TRAY_ID: &str = "main";
+// On Linux the tray is an appindicator, which only opens the menu and never
+// reports clicks, so click actions can't be offered there.
+pub const REPORTS_CLICKS: bool = cfg!(not(target_os = "linux"));
+const APP_NAME: &str = "Qobuz Player";
+// Windows cuts tray tooltips off at 127 characters.
+const MAX_TOOLTIP_CHARS: usize = 120;
+
+// What the menu and tooltip were last built from, so the now-playing
+// heartbeat does not rebuild an open menu every few seconds.
+#[derive(Debug, Clone, PartialEq)]
+struct TrayModel {
+    track: Option<String>,
+    playing: bool,
+    close_to_tray: bool,
+    close_to_tray_locked: bool,
+}
+
+static LAST_MODEL: Mutex<Option<TrayModel>> = Mutex::new(None);
+
+struct Clicks {
+    // Bumped by every click, so a delayed left-click action can tell that a
+    // double click came after it.
+    generation: u64,
+    // Windows reports releasing the second click of a double click as
+    // another click.
+    after_double_click: bool,
+}
+
+static CLICKS: Mutex<Clicks> = Mutex::new(Clicks { generation: 0, after_double_click: false });
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrayAction {
+    None,
+    ToggleWindow,
+    ShowWindow,
+    PlayPause,
+    NextTrack,
+    PreviousTrack,
+    // Only for left click: the menu can only be opened by the platform's
+    // own click handling.
+    OpenMenu,
+}
+
+// What clicking the tray icon does. The defaults match the original
+// behaviour: left click opens the menu and double click restores the window.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayActions {
+    pub left_click: TrayAction,
+    pub double_click: TrayAction,
+    pub middle_click: TrayAction,
+}
+
+impl Default for TrayActions {
+    fn default() -> Self {
+        TrayActions {
+            left_click: TrayAction::OpenMenu,
+            double_click: TrayAction::ShowWindow,
+            middle_click: TrayAction::None,
+        }
+    }
+}
+
+impl TrayActions {
+    pub fn validate(&self) -> Result<(), String> {
+        for (name, action) in [("double_click", self.double_click), ("middle_click", self.middle_click)] {
+            if action == TrayAction::OpenMenu {
+                return Err(format!("open_menu is only available for left_click, not {}", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn track_label(np: &NowPlaying) -> Option<String> {
+    match (&np.artist, &np.title) {
+        (Some(artist), Some(title)) => Some(format!("{} \u{2013} {}", artist, title)),
+        (None, Some(title)) => Some(title.clone()),
+        _ => None,
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('\u{2026}');
+    truncated
+}
+
+fn model(app: &tauri::AppHandle) -> TrayModel {
+    let state = app.state::<crate::AppState>();
+    let np = state.now_playing.lock().map(|np| np.clone()).unwrap_or_default();
+    let close_to_tray = state.settings.lock().map(|s| s.close_to_tray).unwrap_or(true);
+    // Settings pinned by policy, the environment or --set cannot be changed
+    // from the menu either.
+    let close_to_tray_locked = state.config.lock().ok()
+        .and_then(|config| config.resolve().ok())
+        .and_then(|resolved| resolved.sources.get("close_to_tray").copied())
+        .is_some_and(|source| !matches!(source, crate::config::Source::Default | crate::config::Source::File));
+
+    TrayModel {
+        track: track_label(&np),
+        playing: np.state == PlaybackState::Playing,
+        close_to_tray,
+        close_to_tray_locked,
+    }
+}
+
+fn build_menu(app: &tauri::AppHandle, model: &TrayModel) -> tauri::Result<Menu<tauri::Wry>> {
+    let header_text = model.track.as_deref()
+        .map(|track| truncate(track, 60))
+        .unwrap_or_else(|| "Not playing".to_string());
+    let header = MenuItem::with_id(app, "now_playing", header_text, false, None::<&str>)?;
+    let play_pause_label = if model.playing { "Pause" } else { "Play" };
+    let play_pause = MenuItem::with_id(app, "play_pause", play_pause_label, true, None::<&str>)?;
+    let next = MenuItem::with_id(app, "next", "Next", true, None::<&str>)?;
+    let previous = MenuItem::with_id(app, "previous", "Previous", true, None::<&str>)?;
+    let like = MenuItem::with_id(app, "like", "Like", model.track.is_some(), None::<&str>)?;
+    let close_to_tray = CheckMenuItem::with_id(
+        app,
+        "close_to_tray",
+        "Close to tray",
+        !model.close_to_tray_locked,
+        model.close_to_tray,
+        None::<&str>,
+    )?;
+    let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
+    let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    Menu::with_items(app, &[
+        &header,
+        &PredefinedMenuItem::separator(app)?,
+        &play_pause,
+        &next,
+        &previous,
+        &like,
+        &PredefinedMenuItem::separator(app)?,
+        &close_to_tray,
+        &PredefinedMenuItem::separator(app)?,
+        &show,
+        &settings,
+        &quit,
+    ])
+}
+
+fn tooltip(model: &TrayModel) -> String {
+    match &model.track {
+        Some(track) => truncate(track, MAX_TOOLTIP_CHARS),
+        None => APP_NAME.to_string(),
+    }
+}
+
+// Rebuilds the menu and tooltip if anything they show has changed.
+pub fn refresh(app: &tauri::AppHandle) {
+    let model = model(app);
+    {
+        let Ok(mut last) = LAST_MODEL.lock() else {
+            return;
+        };
+        if last.as_ref() == Some(&model) {
+            return;
+        }
+        *last = Some(model.clone());
+    }
+
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return;
+    };
+    match build_menu(app, &model) {
+        Ok(menu) => {
+            let _ = tray.set_menu(Some(menu));
+        }
+        Err(e) => eprintln!("Failed to rebuild tray menu: {}", e),
+    }
+    let _ = tray.set_tooltip(Some(tooltip(&model)));
+}
+
+fn set_close_to_tray(app: &tauri::AppHandle) {
+    let state = app.state::<crate::AppState>();
+    let Ok(mut settings) = state.settings.lock().map(|s| s.clone()) else {
+        return;
+    };
+    settings.close_to_tray = !settings.close_to_tray;
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = crate::save_settings(app.clone(), settings, None).await {
+            eprintln!("Failed to save settings from the tray: {}", e);
+        }
+        // Put the check mark back in line with the settings if saving failed.
+        if let Ok(mut last) = LAST_MODEL.lock() {
+            *last = None;
+        }
+        refresh(&app);
+    });
+}
+
+fn on_menu_event(app: &tauri::AppHandle, id: &str) {
+    let command = match id {
+        "play_pause" => PlayerCommand::Toggle,
+        "next" => PlayerCommand::Next,
+        "previous" => PlayerCommand::Previous,
+        "like" => PlayerCommand::ToggleFavorite,
+        "close_to_tray" => return set_close_to_tray(app),
+        "quit" => crate::control::quit(),
+        "show" => return crate::window_manager::show_main_window(app),
+        "settings" => {
+            let _ = crate::open_settings_window(app.clone());
+            return;
+        }
+        _ => return,
+    };
+    if let Err(e) = crate::player::dispatch(app, &command) {
+        eprintln!("Failed to run tray command: {}", e);
+    }
+}
+
+fn tray_actions(app: &tauri::AppHandle) -> TrayActions {
+    let state = app.state::<crate::AppState>();
+    state.settings.lock().map(|s| s.tray_actions.clone()).unwrap_or_default()
+}
+
+// Click actions are read from the settings on every click; only the menu on
+// left click has to be switched on the tray itself.
+fn apply_actions(app: &tauri::AppHandle) {
+    if let Some(tray) = app.tray_by_id(TRAY_ID) {
+        let _ = tray.set_show_menu_on_left_click(tray_actions(app).left_click == TrayAction::OpenMenu);
+    }
+}
+
+fn close_settings_overlay(window: &tauri::WebviewWindow) {
+    let _ = window.eval(r#"
+        (function() {
+            const overlay = document.getElementById('qobuz-settings-overlay');
+            if (overlay) document.body.removeChild(overlay);
+            const backBtn = document.getElementById('qobuz-settings-back-btn');
+            if (backBtn) document.body.removeChild(backBtn);
+        })();
+    "#);
+}
+
+fn run_action(app: &tauri::AppHandle, action: TrayAction) {
+    let command = match action {
+        TrayAction::None | TrayAction::OpenMenu => return,
+        TrayAction::PlayPause => PlayerCommand::Toggle,
+        TrayAction::NextTrack => PlayerCommand::Next,
+        TrayAction::PreviousTrack => PlayerCommand::Previous,
+        TrayAction::ShowWindow | TrayAction::ToggleWindow => {
+            let Some(window) = app.get_webview_window("main") else {
+                return;
+            };
+            let visible = window.is_visible().unwrap_or(false) && !window.is_minimized().unwrap_or(false);
+            if action == TrayAction::ToggleWindow && visible {
+                let _ = window.hide();
+                return;
+            }
+            close_settings_overlay(&window);
+            crate::window_manager::show_main_window(app);
+            return;
+        }
+    };
+    if let Err(e) = crate::player::dispatch(app, &command) {
+        eprintln!("Failed to run tray action: {}", e);
+    }
+}
+
+// Only Windows reports double clicks on the tray icon, so only there does a
+// left click have to wait to see whether a second one follows.
+#[cfg(target_os = "windows")]
+fn double_click_time() -> Option<Duration> {
+    let millis = unsafe { windows::Win32::UI::Input::KeyboardAndMouse::GetDoubleClickTime() };
+    Some(Duration::from_millis(millis.into()))
+}
+
+#[cfg(not(target_os = "windows"))]
+fn double_click_time() -> Option<Duration> {
+    None
+}
+
+fn on_left_click(app: &tauri::AppHandle, actions: &TrayActions) {
+    let generation = {
+        let Ok(mut clicks) = CLICKS.lock() else {
+            return;
+        };
+        if std::mem::take(&mut clicks.after_double_click) {
+            return;
+        }
+        clicks.generation += 1;
+        clicks.generation
+    };
+    let delay = double_click_time().filter(|_| actions.double_click != TrayAction::None);
+    let Some(delay) = delay else {
+        return run_action(app, actions.left_click);
+    };
+    let app = app.clone();
+    let action = actions.left_click;
+    std::thread::spawn(move || {
+        std::thread::sleep(delay);
+        if CLICKS.lock().is_ok_and(|clicks| clicks.generation == generation) {
+            run_action(&app, action);
+        }
+    });
+}
+
+fn on_double_click(app: &tauri::AppHandle, actions: &TrayActions) {
+    if let Ok(mut clicks) = CLICKS.lock() {
+        clicks.generation += 1;
+        clicks.after_double_click = true;
+    }
+    run_action(app, actions.double_click);
+}
+
+pub fn init(app: &tauri::App) -> tauri::Result<()> {
+    let handle = app.handle();
+    let model = model(handle);
+    let menu = build_menu(handle, &model)?;
+    if let Ok(mut last) = LAST_MODEL.lock() {
+        *last = Some(model.clone());
+    }
+
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(app.default_window_icon().unwrap().clone())
+        .tooltip(tooltip(&model))
+        .menu(&menu)
+        .show_menu_on_left_click(tray_actions(handle).left_click == TrayAction::OpenMenu)
+        .on_menu_event(|app, event| on_menu_event(app, event.id().as_ref()))
+        .on_tray_icon_event(|tray, event| {
+            let app = tray.app_handle();
+            let actions = tray_actions(app);
+            match event {
+                TrayIconEvent::Click { button: MouseButton::Left, button_state: MouseButtonState::Up, .. } => on_left_click(app, &actions),
+                TrayIconEvent::Click { button: MouseButton::Middle, button_state: MouseButtonState::Up, .. } => run_action(app, actions.middle_click),
+                TrayIconEvent::DoubleClick { button: MouseButton::Left, .. } => on_double_click(app, &actions),
+                _ => {}
+            }
+        })
+        .build(app)?;
+
+    crate::tray_icon::update(handle);
+
+    let app = handle.clone();
+    handle.listen(crate::now_playing::CHANGED_EVENT, move |_| {
+        refresh(&app);
+        crate::tray_icon::update(&app);
+    });
+    let app = handle.clone();
+    handle.listen("settings-changed", move |_| {
+        refresh(&app);
+        apply_actions(&app);
+        crate::tray_icon::update(&app);
+    });
+    // The panel usually follows the system theme, so recolour the icon when
+    // it changes.
+    if let Some(window) = handle.get_webview_window("main") {
+        let app = handle.clone();
+        window.on_window_event(move |event| {
+            if let WindowEvent::ThemeChanged(_) = event {
+                crate::tray_icon::update(&app);
+            }
+        });
+    }
+    Ok(())
+}
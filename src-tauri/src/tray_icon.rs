@@ -0,0 +1,237 @@
+use crate::now_playing::PlaybackState;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::Manager;
+
+const LOGO_PNG: &[u8] = include_bytes!("../icons/Square71x71Logo.png");
+const ICON_SIZE: u32 = 32;
+// Size of the logo in the corner of an album-art icon.
+const CORNER_LOGO_SIZE: u32 = 14;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TrayIconSettings {
+    // Draw the logo in a single colour matching the panel instead of the
+    // full-colour app icon.
+    pub monochrome: bool,
+    // Show the current album cover, with the logo in one corner.
+    pub album_art: bool,
+}
+
+impl Default for TrayIconSettings {
+    fn default() -> Self {
+        TrayIconSettings { monochrome: true, album_art: false }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct IconKey {
+    state: PlaybackState,
+    dark_panel: bool,
+    monochrome: bool,
+    cover_url: Option<String>,
+}
+
+struct IconCache {
+    applied: Option<IconKey>,
+    icons: HashMap<IconKey, RgbaImage>,
+    // Only the current cover is kept; a new track replaces it.
+    cover: Option<(String, Option<RgbaImage>)>,
+}
+
+static CACHE: Mutex<Option<IconCache>> = Mutex::new(None);
+
+fn panel_is_dark(app: &tauri::AppHandle) -> bool {
+    #[cfg(target_os = "windows")]
+    {
+        use winreg::RegKey;
+        use winreg::enums::HKEY_CURRENT_USER;
+
+        let _ = app;
+        RegKey::predef(HKEY_CURRENT_USER)
+            .open_subkey("Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize")
+            .and_then(|key| key.get_value::<u32, _>("SystemUsesLightTheme"))
+            .map(|light| light == 0)
+            .unwrap_or(true)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        app.get_webview_window("main")
+            .and_then(|window| window.theme().ok())
+            .map(|theme| theme != tauri::Theme::Light)
+            .unwrap_or(true)
+    }
+}
+
+fn logo(size: u32, monochrome: bool, dark_panel: bool) -> RgbaImage {
+    let mut logo = match image::load_from_memory(LOGO_PNG) {
+        Ok(image) => imageops::resize(&image.to_rgba8(), size, size, FilterType::Lanczos3),
+        Err(e) => {
+            eprintln!("Failed to decode the tray logo: {}", e);
+            return RgbaImage::new(size, size);
+        }
+    };
+
+    if monochrome {
+        // Bright parts of the logo become the glyph, drawn in the colour
+        // that stands out on the panel.
+        let ink = if dark_panel { 255 } else { 0 };
+        for pixel in logo.pixels_mut() {
+            let [r, g, b, a] = pixel.0;
+            let luma = (0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32) / 255.0;
+            *pixel = Rgba([ink, ink, ink, (a as f32 * luma).round() as u8]);
+        }
+    }
+    logo
+}
+
+// Fills a shape given as an inside test, sampling each pixel 4x4 times so
+// the small badge glyphs stay smooth.
+fn fill(canvas: &mut RgbaImage, color: [u8; 3], inside: impl Fn(f32, f32) -> bool) {
+    const SAMPLES: u32 = 4;
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let mut hits = 0;
+        for sy in 0..SAMPLES {
+            for sx in 0..SAMPLES {
+                let px = x as f32 + (sx as f32 + 0.5) / SAMPLES as f32;
+                let py = y as f32 + (sy as f32 + 0.5) / SAMPLES as f32;
+                if inside(px, py) {
+                    hits += 1;
+                }
+            }
+        }
+        if hits == 0 {
+            continue;
+        }
+        let coverage = hits as f32 / (SAMPLES * SAMPLES) as f32;
+        let [r, g, b, a] = pixel.0;
+        let blend = |under: u8, over: u8| (under as f32 * (1.0 - coverage) + over as f32 * coverage).round() as u8;
+        *pixel = Rgba([
+            blend(r, color[0]),
+            blend(g, color[1]),
+            blend(b, color[2]),
+            (a as f32 + (255.0 - a as f32) * coverage).round() as u8,
+        ]);
+    }
+}
+
+fn draw_badge(canvas: &mut RgbaImage, state: PlaybackState, dark_panel: bool) {
+    let (cx, cy, radius) = (24.0_f32, 24.0_f32, 7.5_f32);
+    let (disc, glyph) = if dark_panel { ([255, 255, 255], [0, 0, 0]) } else { ([0, 0, 0], [255, 255, 255]) };
+
+    fill(canvas, disc, |x, y| (x - cx).powi(2) + (y - cy).powi(2) <= radius.powi(2));
+    match state {
+        PlaybackState::Playing => fill(canvas, glyph, |x, y| {
+            // Triangle pointing right, from (21.5, 20) to (28, 24).
+            x >= 21.5 && (y - cy).abs() <= (28.0 - x) * 4.0 / 6.5
+        }),
+        PlaybackState::Paused => fill(canvas, glyph, |x, y| {
+            (20.0..=28.0).contains(&y) && ((20.5..=22.5).contains(&x) || (25.5..=27.5).contains(&x))
+        }),
+        PlaybackState::Stopped => {}
+    }
+}
+
+fn compose(key: &IconKey, cover: Option<&RgbaImage>) -> RgbaImage {
+    let mut canvas = match cover {
+        Some(cover) => {
+            let mut canvas = imageops::resize(cover, ICON_SIZE, ICON_SIZE, FilterType::Lanczos3);
+            let corner = logo(CORNER_LOGO_SIZE, key.monochrome, true);
+            imageops::overlay(&mut canvas, &corner, 0, 0);
+            canvas
+        }
+        None => logo(ICON_SIZE, key.monochrome, key.dark_panel),
+    };
+    draw_badge(&mut canvas, key.state, key.dark_panel);
+    canvas
+}
+
+fn fetch_cover(url: &str) -> Result<RgbaImage, String> {
+    let bytes = crate::covers::fetch(url)?;
+    image::load_from_memory(&bytes)
+        .map(|image| image.to_rgba8())
+        .map_err(|e| format!("Failed to decode cover {}: {}", url, e))
+}
+
+// Brings the tray icon in line with the playback state, settings and panel
+// theme. Icons are composed once per state and cached; covers are downloaded
+// in the background and the icon is updated again once one arrives.
+pub fn update(app: &tauri::AppHandle) {
+    let state = app.state::<crate::AppState>();
+    let np = state.now_playing.lock().map(|np| np.clone()).unwrap_or_default();
+    let settings = state.settings.lock().map(|s| s.tray_icon.clone()).unwrap_or_default();
+
+    let key = IconKey {
+        state: np.state,
+        dark_panel: panel_is_dark(app),
+        monochrome: settings.monochrome,
+        cover_url: np.cover_url.filter(|url| settings.album_art && url.starts_with("https://")),
+    };
+
+    // Only the icon is built under the lock: set_icon waits on the main
+    // thread, which may itself be waiting for the cache.
+    let icon = {
+        let Ok(mut cache) = CACHE.lock() else {
+            return;
+        };
+        let cache = cache.get_or_insert_with(|| IconCache { applied: None, icons: HashMap::new(), cover: None });
+        if cache.applied.as_ref() == Some(&key) {
+            return;
+        }
+
+        if let Some(url) = &key.cover_url
+            && cache.cover.as_ref().map(|(cached, _)| cached) != Some(url) {
+                cache.cover = Some((url.clone(), None));
+                cache.icons.retain(|k, _| k.cover_url.is_none());
+                let app = app.clone();
+                let url = url.clone();
+                std::thread::spawn(move || {
+                    let cover = fetch_cover(&url).map_err(|e| eprintln!("{}", e)).ok();
+                    if let Ok(mut cache) = CACHE.lock()
+                        && let Some(cache) = cache.as_mut()
+                        && let Some((cached, image)) = cache.cover.as_mut()
+                        && *cached == url {
+                            *image = cover;
+                            cache.applied = None;
+                        }
+                    update(&app);
+                });
+            }
+
+        let cover = match (&key.cover_url, &cache.cover) {
+            (Some(url), Some((cached, Some(image)))) if url == cached => Some(image),
+            _ => None,
+        };
+        // Until the cover arrives, show the logo icon without caching it under
+        // the album-art key.
+        let icon = match cover {
+            Some(cover) => cache.icons.entry(key.clone()).or_insert_with(|| compose(&key, Some(cover))).clone(),
+            None => {
+                let logo_key = IconKey { cover_url: None, ..key.clone() };
+                cache.icons.entry(logo_key.clone()).or_insert_with(|| compose(&logo_key, None)).clone()
+            }
+        };
+        // Marked before setting so a concurrent update with the same key
+        // does not set it twice; a pending cover download clears this again
+        // once it finishes.
+        cache.applied = Some(key.clone());
+        icon
+    };
+
+    let Some(tray) = app.tray_by_id(crate::tray::TRAY_ID) else {
+        return;
+    };
+    let (width, height) = icon.dimensions();
+    let image = tauri::image::Image::new_owned(icon.into_raw(), width, height);
+    if let Err(e) = tray.set_icon(Some(image)) {
+        eprintln!("Failed to update tray icon: {}", e);
+        if let Ok(mut cache) = CACHE.lock()
+            && let Some(cache) = cache.as_mut()
+            && cache.applied.as_ref() == Some(&key) {
+                cache.applied = None;
+            }
+    }
+}
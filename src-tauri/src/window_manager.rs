@@ -126,3 +126,66 @@ pub mod windows_impl {
 }
 
 pub use windows_impl::*;
+
+// The main window is declared in tauri.conf.json with "create": false so it
+// can be built here with the webview data directory chosen by `paths`.
+pub fn create_main_window(app: &tauri::App) -> Result<tauri::WebviewWindow, String> {
+    let window_config = app.config().app.windows.iter()
+        .find(|w| w.label == "main")
+        .cloned()
+        .ok_or_else(|| "Main window config not found".to_string())?;
+
+    let mut builder = tauri::WebviewWindowBuilder::from_config(app.handle(), &window_config)
+        .map_err(|e| format!("Failed to configure main window: {}", e))?
+        .initialization_script(crate::now_playing::OBSERVER_JS)
+        .initialization_script(crate::keymap::LISTENER_JS);
+    if let Some(dir) = crate::paths::webview_data_dir()? {
+        builder = builder.data_directory(dir);
+    }
+
+    builder.build().map_err(|e| format!("Failed to create main window: {}", e))
+}
+
+pub fn apply_launch_mode(window: &tauri::WebviewWindow, launch_mode: &crate::settings::LaunchMode) {
+    use crate::settings::LaunchMode;
+
+    match launch_mode {
+        LaunchMode::Restored => {
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+        LaunchMode::Minimized => {
+            let _ = window.show();
+            let _ = window.minimize();
+        }
+        LaunchMode::MinimizedToTray => {
+            let _ = window.hide();
+        }
+        LaunchMode::Maximized => {
+            let _ = window.maximize();
+            let _ = window.show();
+            let _ = window.set_focus();
+        }
+    }
+}
+
+pub fn show_main_window(app: &tauri::AppHandle) {
+    use tauri::Manager;
+
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+
+        #[cfg(target_os = "windows")]
+        {
+            use raw_window_handle::HasWindowHandle;
+
+            if let Ok(wh) = window.window_handle()
+                && let raw_window_handle::RawWindowHandle::Win32(h) = wh.into() {
+                    crate::thumbar::set_stored_hwnd(h);
+                    crate::thumbar::add_thumb_buttons();
+                }
+        }
+    }
+}